@@ -3,6 +3,11 @@
 //! This helps the user interact with the editor in a very natural way and for the agent
 //! to understand the different steps the user has taken to get a task done
 
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufRead, BufReader, Write};
+use std::path::Path;
+
 use crate::chunking::text_document::{Position, Range};
 
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
@@ -10,11 +15,42 @@ pub struct OpenFileContextEvent {
     pub fs_file_path: String,
 }
 
+/// Where a `Goto*` interaction resolved to.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct GotoTarget {
+    pub fs_file_path: String,
+    pub range: Range,
+}
+
+/// What LSP interaction a [`LSPContextEvent`] captures, modelled on the
+/// actual LSP request surface instead of an opaque string - so the agent can
+/// reason about *what* the user was investigating (a goto-definition to a
+/// specific file and range, a rename to a specific new name, ...) rather than
+/// just "some LSP thing happened at a position." Tagged with an explicit
+/// `type` key (plus `payload` for the variants that carry one) so an
+/// editor-specific interaction we don't model yet still round-trips via
+/// [`LspInteractionKind::Other`] instead of failing to deserialize.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(tag = "type", content = "payload", rename_all = "camelCase")]
+pub enum LspInteractionKind {
+    Hover,
+    GotoDefinition(GotoTarget),
+    GotoTypeDefinition(GotoTarget),
+    GotoImplementation(GotoTarget),
+    FindReferences,
+    DocumentSymbol { symbol_name: String },
+    Completion,
+    SignatureHelp,
+    CodeAction,
+    Rename { new_name: String },
+    Other(String),
+}
+
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub struct LSPContextEvent {
     pub fs_file_path: String,
     pub position: Position,
-    pub event_type: String,
+    pub interaction: LspInteractionKind,
 }
 
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
@@ -23,11 +59,628 @@ pub struct SelectionContextEvent {
     pub range: Range,
 }
 
-/// All the context-driven events which can happen in the editor that are useful
-/// and done by the user in a quest to provide additional context to the agent.
+/// Which access mode an [`FileAccessKind::Access`] event happened under,
+/// borrowed from the `notify` crate's access taxonomy.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum FileAccessMode {
+    Read,
+    Execute,
+    Any,
+}
+
+/// Which mode a [`FileAccessKind::Close`] event closed the file under -
+/// `notify` also distinguishes `Execute` here, but a closed-after-executing
+/// file isn't a context signal this crate's consumers act on, so we only
+/// track the two that are: was the file being read, or had it just been
+/// written to.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum FileCloseMode {
+    Read,
+    Write,
+}
+
+/// What changed about a file under a [`FileAccessKind::Modify`] event: its
+/// contents (`Data`), its path (`Name`, carrying the old and new path), or
+/// just its metadata (permissions, timestamps, ...) without touching either.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(tag = "kind", content = "detail", rename_all = "kebab-case")]
+pub enum FileModifyKind {
+    Data,
+    Name { from: String, to: String },
+    Metadata,
+}
+
+/// The hierarchical file-access taxonomy `notify` uses to describe
+/// filesystem events, trimmed to the shapes this crate's context stream
+/// cares about: a file being opened/read/executed (`Access`), having its
+/// contents, path or metadata change (`Modify`), being created or removed
+/// outright, or being closed after a read or a write.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(tag = "kind", content = "mode", rename_all = "kebab-case")]
+pub enum FileAccessKind {
+    Access(FileAccessMode),
+    Modify(FileModifyKind),
+    Create,
+    Remove,
+    Close(FileCloseMode),
+}
+
+/// One entry in the filesystem audit trail: `fs_file_path` had `kind` happen
+/// to it. Together with [`OpenFileContextEvent`] (kept separate since "the
+/// user opened this file" is by far the most common signal) this lets a
+/// recording reconstruct saves, creates, deletes and renames, not just opens.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct FileSystemContextEvent {
+    pub fs_file_path: String,
+    pub kind: FileAccessKind,
+}
+
+/// Where a [`ContextEvent`] originated - the human interacting directly
+/// through the editor UI, a shell command the user ran, the agent acting on
+/// its own, or a keybinding-triggered action.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ContextEventSource {
+    Editor,
+    Terminal,
+    Agent,
+    Keybinding,
+}
+
+/// One classifiable fact about a [`ContextEvent`], following watchexec's
+/// `Tag` model: cheap, filterable pieces of "what kind of event is this and
+/// what does it touch," as distinct from [`ContextEvent::metadata`]'s
+/// free-form, non-filterable annotations. The payloads that used to be the
+/// sole arms of a bare `ContextGatheringEvent` enum (`Open`/`Lsp`/
+/// `Selection`/`FileSystem`) live on here as tags instead, so a single event
+/// can now carry more than one classification - e.g. both the `Path` it
+/// touched and the `Source` it came from - rather than being pigeonholed
+/// into exactly one enum arm.
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
-pub enum ContextGatheringEvent {
-    OpenFile(OpenFileContextEvent),
-    LSPContextEvent(LSPContextEvent),
+#[serde(tag = "kind", content = "value", rename_all = "kebab-case")]
+pub enum ContextTag {
+    Path(String),
+    Source(ContextEventSource),
+    Open(OpenFileContextEvent),
+    Lsp(LSPContextEvent),
     Selection(SelectionContextEvent),
+    FileSystem(FileSystemContextEvent),
+}
+
+impl ContextTag {
+    /// A short, stable discriminant for this tag's kind, independent of its
+    /// payload - the string [`ContextEvent::select_by_kind`] filters on.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            ContextTag::Path(_) => "path",
+            ContextTag::Source(_) => "source",
+            ContextTag::Open(_) => "open",
+            ContextTag::Lsp(_) => "lsp",
+            ContextTag::Selection(_) => "selection",
+            ContextTag::FileSystem(_) => "file-system",
+        }
+    }
+}
+
+/// A single context-gathering event, following watchexec's
+/// `Event { tags, metadata }` design: the classifiable `tags` it carries
+/// (see [`ContextTag`]) plus an arbitrary `metadata` map for annotations
+/// that aren't worth their own tag kind - a git branch, a caret count, the
+/// active language server id - because nothing needs to filter on them, just
+/// read them back once the event of interest has already been found.
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize)]
+pub struct ContextEvent {
+    pub tags: Vec<ContextTag>,
+    #[serde(default)]
+    pub metadata: HashMap<String, Vec<String>>,
+}
+
+impl ContextEvent {
+    pub fn new(tags: Vec<ContextTag>) -> Self {
+        Self {
+            tags,
+            metadata: HashMap::new(),
+        }
+    }
+
+    /// Whether any tag marks this event as having come from `source`.
+    pub fn has_source(&self, source: ContextEventSource) -> bool {
+        self.tags.iter().any(|tag| matches!(tag, ContextTag::Source(tag_source) if *tag_source == source))
+    }
+
+    /// Every file path this event touches, across its `Path` tag and every
+    /// payload tag that carries its own `fs_file_path`.
+    pub fn paths(&self) -> Vec<&str> {
+        self.tags
+            .iter()
+            .filter_map(|tag| match tag {
+                ContextTag::Path(path) => Some(path.as_str()),
+                ContextTag::Open(open) => Some(open.fs_file_path.as_str()),
+                ContextTag::Lsp(lsp) => Some(lsp.fs_file_path.as_str()),
+                ContextTag::Selection(selection) => Some(selection.fs_file_path.as_str()),
+                ContextTag::FileSystem(file_system) => Some(file_system.fs_file_path.as_str()),
+                ContextTag::Source(_) => None,
+            })
+            .collect()
+    }
+
+    /// Every tag matching `kind` (see [`ContextTag::kind`]), so downstream
+    /// code can cheaply query a recording without exhaustively matching the
+    /// tag enum itself.
+    pub fn select_by_kind(&self, kind: &str) -> Vec<&ContextTag> {
+        self.tags.iter().filter(|tag| tag.kind() == kind).collect()
+    }
+}
+
+#[derive(Debug)]
+pub enum ContextRecordingError {
+    Io(std::io::Error),
+    InvalidJson(usize, String),
+}
+
+/// Session-level metadata for a [`ContextRecording`], written as the
+/// recording's optional first line. Kept separate from [`ContextEvent`] so a
+/// reader can tell "no header was written" apart from "the header happened
+/// to be an empty event."
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize)]
+pub struct ContextRecordingHeader {
+    pub session_id: String,
+    #[serde(default)]
+    pub metadata: HashMap<String, String>,
+}
+
+/// An append-only, newline-delimited JSON recording of a context session,
+/// modelled on Sentry's envelope format: an optional header line followed by
+/// one serialized [`ContextEvent`] per line. The NDJSON framing means a
+/// recording truncated mid-write (a crash, a killed process) still parses as
+/// a valid prefix of events instead of one corrupt blob.
+#[derive(Debug, Clone, Default)]
+pub struct ContextRecording {
+    pub header: Option<ContextRecordingHeader>,
+    pub events: Vec<ContextEvent>,
+}
+
+impl ContextRecording {
+    pub fn new(header: Option<ContextRecordingHeader>) -> Self {
+        Self {
+            header,
+            events: Vec::new(),
+        }
+    }
+
+    pub fn from_path(path: &Path) -> Result<Self, ContextRecordingError> {
+        let file = File::open(path).map_err(ContextRecordingError::Io)?;
+        Self::from_reader(BufReader::new(file))
+    }
+
+    pub fn from_slice(bytes: &[u8]) -> Result<Self, ContextRecordingError> {
+        Self::from_reader(bytes)
+    }
+
+    /// Parses `reader` line by line rather than reading the whole stream into
+    /// one buffer first. Every parsed event is collected into `events`, so the
+    /// returned recording still holds the entire session in memory.
+    fn from_reader<R: BufRead>(reader: R) -> Result<Self, ContextRecordingError> {
+        let mut header = None;
+        let mut events = Vec::new();
+        for (line_number, line) in reader.lines().enumerate() {
+            let line = line.map_err(ContextRecordingError::Io)?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            if line_number == 0 && header.is_none() {
+                if let Ok(parsed_header) = serde_json::from_str::<ContextRecordingHeader>(&line) {
+                    header = Some(parsed_header);
+                    continue;
+                }
+            }
+            let event = serde_json::from_str::<ContextEvent>(&line)
+                .map_err(|err| ContextRecordingError::InvalidJson(line_number, err.to_string()))?;
+            events.push(event);
+        }
+        Ok(Self { header, events })
+    }
+
+    /// Appends `event` to the in-memory recording. This does not by itself
+    /// persist anything - call [`Self::to_writer`] to write the recording out;
+    /// it always writes the header and every event, not just the new tail.
+    pub fn append_event(&mut self, event: ContextEvent) {
+        self.events.push(event);
+    }
+
+    /// Streams the recording out as newline-delimited JSON, flushing after
+    /// every line so a crash mid-session leaves a valid, replayable prefix
+    /// behind rather than a dangling partial write.
+    pub fn to_writer<W: Write>(&self, mut writer: W) -> Result<(), ContextRecordingError> {
+        if let Some(header) = &self.header {
+            let serialized = serde_json::to_string(header)
+                .map_err(|err| ContextRecordingError::InvalidJson(0, err.to_string()))?;
+            writeln!(writer, "{}", serialized).map_err(ContextRecordingError::Io)?;
+            writer.flush().map_err(ContextRecordingError::Io)?;
+        }
+        for (index, event) in self.events.iter().enumerate() {
+            let serialized = serde_json::to_string(event)
+                .map_err(|err| ContextRecordingError::InvalidJson(index + 1, err.to_string()))?;
+            writeln!(writer, "{}", serialized).map_err(ContextRecordingError::Io)?;
+            writer.flush().map_err(ContextRecordingError::Io)?;
+        }
+        Ok(())
+    }
+}
+
+/// A monotonic position in an event stream, following measureme's
+/// timestamped-event-stream model. `seq` is what ordering and replay are
+/// actually driven by - it's assigned gap-free by
+/// [`ContextEventStream::push`] and stays correct even across system clock
+/// adjustments. `monotonic_nanos` is a clock-adjustment-proof offset used
+/// for debounce math in [`ContextEventStream::coalesce`]. `wall_clock_ms` is
+/// best-effort context for a human reading a recording and is never used to
+/// decide ordering.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct EventStamp {
+    pub seq: u64,
+    pub monotonic_nanos: u64,
+    pub wall_clock_ms: Option<u64>,
+}
+
+/// A [`ContextEvent`] paired with the [`EventStamp`] it was given on arrival
+/// at a [`ContextEventStream`].
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct StampedContextEvent {
+    pub stamp: EventStamp,
+    pub event: ContextEvent,
+}
+
+/// Collects [`ContextEvent`]s in arrival order, stamping each with a
+/// gap-free `seq` ordinal and a monotonic timing offset so replay stays
+/// deterministic regardless of wall-clock jumps.
+pub struct ContextEventStream {
+    origin: std::time::Instant,
+    next_seq: u64,
+    events: Vec<StampedContextEvent>,
+}
+
+impl ContextEventStream {
+    pub fn new() -> Self {
+        Self {
+            origin: std::time::Instant::now(),
+            next_seq: 0,
+            events: Vec::new(),
+        }
+    }
+
+    /// Stamps `event` with the next sequence ordinal and the elapsed
+    /// monotonic time since the stream was created, then appends it.
+    pub fn push(&mut self, event: ContextEvent) -> EventStamp {
+        let stamp = EventStamp {
+            seq: self.next_seq,
+            monotonic_nanos: self.origin.elapsed().as_nanos() as u64,
+            wall_clock_ms: std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .ok()
+                .map(|duration| duration.as_millis() as u64),
+        };
+        self.next_seq += 1;
+        self.events.push(StampedContextEvent { stamp, event });
+        stamp
+    }
+
+    /// Every stamped event in arrival (== `seq`) order.
+    pub fn iter_ordered(&self) -> impl Iterator<Item = &StampedContextEvent> {
+        self.events.iter()
+    }
+
+    /// Every stamped event whose `seq` falls in `start..=end`.
+    pub fn between(&self, start: u64, end: u64) -> Vec<&StampedContextEvent> {
+        self.events
+            .iter()
+            .filter(|stamped| stamped.stamp.seq >= start && stamped.stamp.seq <= end)
+            .collect()
+    }
+
+    /// Collapses rapid-fire `Open`/`Selection` duplicates that touch the
+    /// same path within `debounce_nanos` of the previously kept event of the
+    /// same kind, so a replay isn't dominated by cursor-wiggle noise. Every
+    /// other tag kind is always kept.
+    pub fn coalesce(&self, debounce_nanos: u64) -> Vec<&StampedContextEvent> {
+        let mut kept: Vec<&StampedContextEvent> = Vec::new();
+        for stamped in &self.events {
+            if Self::is_debounced_duplicate(&kept, stamped, debounce_nanos) {
+                continue;
+            }
+            kept.push(stamped);
+        }
+        kept
+    }
+
+    fn is_debounced_duplicate(
+        kept: &[&StampedContextEvent],
+        candidate: &StampedContextEvent,
+        debounce_nanos: u64,
+    ) -> bool {
+        let Some(candidate_kind) = Self::debounce_kind(candidate) else {
+            return false;
+        };
+        kept.iter().rev().any(|previous| {
+            Self::debounce_kind(previous) == Some(candidate_kind)
+                && candidate.stamp.monotonic_nanos >= previous.stamp.monotonic_nanos
+                && candidate.stamp.monotonic_nanos - previous.stamp.monotonic_nanos
+                    <= debounce_nanos
+        })
+    }
+
+    /// The `(tag kind, path)` identity used to decide whether two events are
+    /// "the same rapid-fire signal," or `None` if `event` isn't a debounce
+    /// candidate at all.
+    fn debounce_kind(stamped: &StampedContextEvent) -> Option<(&'static str, &str)> {
+        stamped.event.tags.iter().find_map(|tag| match tag {
+            ContextTag::Open(open) => Some(("open", open.fs_file_path.as_str())),
+            ContextTag::Selection(selection) => {
+                Some(("selection", selection.fs_file_path.as_str()))
+            }
+            _ => None,
+        })
+    }
+}
+
+impl Default for ContextEventStream {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lsp_interaction_kind_round_trips_a_payload_carrying_variant() {
+        let interaction = LspInteractionKind::GotoDefinition(GotoTarget {
+            fs_file_path: "src/main.rs".to_owned(),
+            range: Range::new(Position::new(1, 0, 0), Position::new(1, 5, 0)),
+        });
+        let serialized = serde_json::to_string(&interaction).unwrap();
+        assert!(serialized.contains("\"type\":\"gotoDefinition\""));
+        let round_tripped: LspInteractionKind = serde_json::from_str(&serialized).unwrap();
+        match round_tripped {
+            LspInteractionKind::GotoDefinition(target) => {
+                assert_eq!(target.fs_file_path, "src/main.rs");
+            }
+            other => panic!("expected GotoDefinition, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_lsp_interaction_kind_round_trips_a_unit_variant() {
+        let serialized = serde_json::to_string(&LspInteractionKind::Hover).unwrap();
+        let round_tripped: LspInteractionKind = serde_json::from_str(&serialized).unwrap();
+        assert!(matches!(round_tripped, LspInteractionKind::Hover));
+    }
+
+    #[test]
+    fn test_lsp_interaction_kind_falls_back_to_other_for_an_unmodelled_interaction() {
+        let serialized = serde_json::to_string(&LspInteractionKind::Other("foldingRange".to_owned())).unwrap();
+        let round_tripped: LspInteractionKind = serde_json::from_str(&serialized).unwrap();
+        match round_tripped {
+            LspInteractionKind::Other(kind) => assert_eq!(kind, "foldingRange"),
+            other => panic!("expected Other, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_file_access_kind_round_trips_a_rename() {
+        let kind = FileAccessKind::Modify(FileModifyKind::Name {
+            from: "old.rs".to_owned(),
+            to: "new.rs".to_owned(),
+        });
+        let serialized = serde_json::to_string(&kind).unwrap();
+        assert!(serialized.contains("\"kind\":\"modify\""));
+        let round_tripped: FileAccessKind = serde_json::from_str(&serialized).unwrap();
+        match round_tripped {
+            FileAccessKind::Modify(FileModifyKind::Name { from, to }) => {
+                assert_eq!(from, "old.rs");
+                assert_eq!(to, "new.rs");
+            }
+            other => panic!("expected Modify(Name), got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_file_access_kind_round_trips_an_access_mode() {
+        let kind = FileAccessKind::Access(FileAccessMode::Execute);
+        let serialized = serde_json::to_string(&kind).unwrap();
+        let round_tripped: FileAccessKind = serde_json::from_str(&serialized).unwrap();
+        assert!(matches!(
+            round_tripped,
+            FileAccessKind::Access(FileAccessMode::Execute)
+        ));
+    }
+
+    #[test]
+    fn test_file_system_context_event_round_trips_through_json() {
+        let event = FileSystemContextEvent {
+            fs_file_path: "src/lib.rs".to_owned(),
+            kind: FileAccessKind::Close(FileCloseMode::Write),
+        };
+        let serialized = serde_json::to_string(&event).unwrap();
+        let round_tripped: FileSystemContextEvent = serde_json::from_str(&serialized).unwrap();
+        assert_eq!(round_tripped.fs_file_path, "src/lib.rs");
+        assert!(matches!(
+            round_tripped.kind,
+            FileAccessKind::Close(FileCloseMode::Write)
+        ));
+    }
+
+    #[test]
+    fn test_context_tag_kind_is_stable_across_every_variant() {
+        assert_eq!(ContextTag::Path("a.rs".to_owned()).kind(), "path");
+        assert_eq!(ContextTag::Source(ContextEventSource::Agent).kind(), "source");
+        assert_eq!(
+            ContextTag::Open(OpenFileContextEvent {
+                fs_file_path: "a.rs".to_owned()
+            })
+            .kind(),
+            "open"
+        );
+        assert_eq!(
+            ContextTag::Selection(SelectionContextEvent {
+                fs_file_path: "a.rs".to_owned(),
+                range: Range::new(Position::new(0, 0, 0), Position::new(0, 1, 0)),
+            })
+            .kind(),
+            "selection"
+        );
+        assert_eq!(
+            ContextTag::FileSystem(FileSystemContextEvent {
+                fs_file_path: "a.rs".to_owned(),
+                kind: FileAccessKind::Create,
+            })
+            .kind(),
+            "file-system"
+        );
+    }
+
+    #[test]
+    fn test_context_event_has_source_checks_only_the_source_tag() {
+        let event = ContextEvent::new(vec![
+            ContextTag::Path("a.rs".to_owned()),
+            ContextTag::Source(ContextEventSource::Terminal),
+        ]);
+        assert!(event.has_source(ContextEventSource::Terminal));
+        assert!(!event.has_source(ContextEventSource::Editor));
+    }
+
+    #[test]
+    fn test_context_event_paths_collects_from_every_payload_tag() {
+        let event = ContextEvent::new(vec![
+            ContextTag::Path("a.rs".to_owned()),
+            ContextTag::Open(OpenFileContextEvent {
+                fs_file_path: "b.rs".to_owned(),
+            }),
+            ContextTag::Source(ContextEventSource::Editor),
+        ]);
+        assert_eq!(event.paths(), vec!["a.rs", "b.rs"]);
+    }
+
+    #[test]
+    fn test_context_event_select_by_kind_filters_to_matching_tags_only() {
+        let event = ContextEvent::new(vec![
+            ContextTag::Path("a.rs".to_owned()),
+            ContextTag::Source(ContextEventSource::Editor),
+            ContextTag::Path("b.rs".to_owned()),
+        ]);
+        let paths = event.select_by_kind("path");
+        assert_eq!(paths.len(), 2);
+        assert!(event.select_by_kind("lsp").is_empty());
+    }
+
+    #[test]
+    fn test_context_recording_from_slice_parses_a_header_followed_by_events() {
+        let ndjson = concat!(
+            "{\"session_id\":\"session-1\"}\n",
+            "{\"tags\":[{\"kind\":\"path\",\"value\":\"a.rs\"}]}\n",
+            "{\"tags\":[{\"kind\":\"path\",\"value\":\"b.rs\"}]}\n",
+        );
+        let recording = ContextRecording::from_slice(ndjson.as_bytes()).unwrap();
+        assert_eq!(recording.header.unwrap().session_id, "session-1");
+        assert_eq!(recording.events.len(), 2);
+    }
+
+    #[test]
+    fn test_context_recording_from_slice_tolerates_no_header_line() {
+        let ndjson = "{\"tags\":[]}\n";
+        let recording = ContextRecording::from_slice(ndjson.as_bytes()).unwrap();
+        assert!(recording.header.is_none());
+        assert_eq!(recording.events.len(), 1);
+    }
+
+    #[test]
+    fn test_context_recording_from_slice_surfaces_the_offending_line_on_invalid_json() {
+        let ndjson = "{\"session_id\":\"session-1\"}\nnot json\n";
+        let error = ContextRecording::from_slice(ndjson.as_bytes()).unwrap_err();
+        match error {
+            ContextRecordingError::InvalidJson(line_number, _) => assert_eq!(line_number, 1),
+            other => panic!("expected InvalidJson, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_context_recording_to_writer_round_trips_through_from_slice() {
+        let mut recording = ContextRecording::new(Some(ContextRecordingHeader {
+            session_id: "session-2".to_owned(),
+            metadata: HashMap::new(),
+        }));
+        recording.append_event(ContextEvent::new(vec![ContextTag::Path("a.rs".to_owned())]));
+        recording.append_event(ContextEvent::new(vec![ContextTag::Path("b.rs".to_owned())]));
+
+        let mut buffer = Vec::new();
+        recording.to_writer(&mut buffer).unwrap();
+
+        let round_tripped = ContextRecording::from_slice(&buffer).unwrap();
+        assert_eq!(round_tripped.header.unwrap().session_id, "session-2");
+        assert_eq!(round_tripped.events.len(), 2);
+        assert_eq!(round_tripped.events[1].paths(), vec!["b.rs"]);
+    }
+
+    #[test]
+    fn test_context_event_stream_push_stamps_a_gap_free_sequence() {
+        let mut stream = ContextEventStream::new();
+        let first = stream.push(ContextEvent::new(vec![]));
+        let second = stream.push(ContextEvent::new(vec![]));
+        let third = stream.push(ContextEvent::new(vec![]));
+        assert_eq!((first.seq, second.seq, third.seq), (0, 1, 2));
+    }
+
+    #[test]
+    fn test_context_event_stream_between_filters_by_seq_range() {
+        let mut stream = ContextEventStream::new();
+        for _ in 0..5 {
+            stream.push(ContextEvent::new(vec![]));
+        }
+        let middle = stream.between(1, 3);
+        assert_eq!(
+            middle.iter().map(|stamped| stamped.stamp.seq).collect::<Vec<_>>(),
+            vec![1, 2, 3]
+        );
+    }
+
+    #[test]
+    fn test_context_event_stream_coalesce_drops_a_rapid_fire_duplicate_open() {
+        let mut stream = ContextEventStream::new();
+        stream.push(ContextEvent::new(vec![ContextTag::Open(OpenFileContextEvent {
+            fs_file_path: "a.rs".to_owned(),
+        })]));
+        stream.push(ContextEvent::new(vec![ContextTag::Open(OpenFileContextEvent {
+            fs_file_path: "a.rs".to_owned(),
+        })]));
+
+        let kept = stream.coalesce(u64::MAX);
+        assert_eq!(kept.len(), 1);
+    }
+
+    #[test]
+    fn test_context_event_stream_coalesce_keeps_opens_of_different_paths() {
+        let mut stream = ContextEventStream::new();
+        stream.push(ContextEvent::new(vec![ContextTag::Open(OpenFileContextEvent {
+            fs_file_path: "a.rs".to_owned(),
+        })]));
+        stream.push(ContextEvent::new(vec![ContextTag::Open(OpenFileContextEvent {
+            fs_file_path: "b.rs".to_owned(),
+        })]));
+
+        let kept = stream.coalesce(u64::MAX);
+        assert_eq!(kept.len(), 2);
+    }
+
+    #[test]
+    fn test_context_event_stream_coalesce_never_drops_non_debounced_tag_kinds() {
+        let mut stream = ContextEventStream::new();
+        stream.push(ContextEvent::new(vec![ContextTag::Path("a.rs".to_owned())]));
+        stream.push(ContextEvent::new(vec![ContextTag::Path("a.rs".to_owned())]));
+
+        let kept = stream.coalesce(u64::MAX);
+        assert_eq!(kept.len(), 2);
+    }
 }
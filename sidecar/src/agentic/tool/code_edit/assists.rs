@@ -0,0 +1,331 @@
+//! Deterministic, tree-sitter-driven stand-ins for the mechanical refactors
+//! that `format_prompt` would otherwise round-trip through an LLM for.
+//! `find_assist` is the entry point and returns `None` when nothing here
+//! matches; the broker does not call it, so a caller that wants an assist
+//! tried before a `CodeEditPromptFormatters` implementation calls it itself -
+//! each assist here is scoped narrowly enough that a match is unambiguous,
+//! so there's no model drift to worry about.
+
+use tree_sitter::{Node, Parser, Tree};
+
+use super::types::CodeEdit;
+
+/// A precise, single-region edit an assist wants applied: a byte range into
+/// `code_to_edit` plus its replacement, never a full-file rewrite.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TextEdit {
+    pub range: std::ops::Range<usize>,
+    pub replacement: String,
+}
+
+impl TextEdit {
+    pub fn new(range: std::ops::Range<usize>, replacement: String) -> Self {
+        Self { range, replacement }
+    }
+
+    /// Applies this edit to `source`, returning the rewritten string.
+    pub fn apply(&self, source: &str) -> String {
+        let mut rewritten = String::with_capacity(source.len());
+        rewritten.push_str(&source[..self.range.start]);
+        rewritten.push_str(&self.replacement);
+        rewritten.push_str(&source[self.range.end..]);
+        rewritten
+    }
+}
+
+/// Builds a `Parser` for `language`, or `None` for anything we don't have a
+/// deterministic assist library for - those selections always fall through
+/// to the LLM formatter.
+fn parser_for_language(language: &str) -> Option<Parser> {
+    let mut parser = Parser::new();
+    let grammar = match language {
+        "rust" => tree_sitter_rust::language(),
+        _ => return None,
+    };
+    parser.set_language(grammar).ok()?;
+    Some(parser)
+}
+
+/// Tries every assist against `context.code_to_edit()` in turn and returns
+/// the first match. Ordered roughly by how unambiguous the match is, so the
+/// narrow single-token checks run before the ones that have to walk the
+/// whole selection looking for a pattern.
+pub fn find_assist(context: &CodeEdit) -> Option<TextEdit> {
+    let mut parser = parser_for_language(context.language())?;
+    let source = context.code_to_edit();
+    let tree = parser.parse(source, None)?;
+
+    change_visibility(source, &tree)
+        .or_else(|| add_derive(source, &tree, context.instruction()))
+        .or_else(|| split_import(source, &tree))
+        .or_else(|| replace_if_let_with_match(source, &tree))
+        .or_else(|| introduce_variable(source, &tree, context.instruction()))
+}
+
+/// Toggles `pub` on the first item in the selection: adds it if missing,
+/// removes it (and the trailing space) if present.
+fn change_visibility(source: &str, tree: &Tree) -> Option<TextEdit> {
+    let item = first_named_child(tree.root_node())?;
+    let mut cursor = item.walk();
+    let visibility = item
+        .children(&mut cursor)
+        .find(|child| child.kind() == "visibility_modifier");
+    if let Some(visibility) = visibility {
+        let mut end = visibility.end_byte();
+        while source.as_bytes().get(end) == Some(&b' ') {
+            end += 1;
+        }
+        return Some(TextEdit::new(visibility.start_byte()..end, String::new()));
+    }
+
+    let insertable = matches!(
+        item.kind(),
+        "struct_item" | "enum_item" | "function_item" | "const_item" | "mod_item" | "type_item"
+    );
+    if !insertable {
+        return None;
+    }
+    Some(TextEdit::new(
+        item.start_byte()..item.start_byte(),
+        "pub ".to_owned(),
+    ))
+}
+
+/// Inserts `#[derive(...)]` above the selection's struct/enum, naming
+/// whichever common derives (`Debug`, `Clone`, `Copy`, `PartialEq`, `Eq`,
+/// `Hash`, `Default`, `Serialize`, `Deserialize`) the instruction names as a
+/// whole word. Returns
+/// `None` if the selection isn't a struct/enum or the instruction doesn't
+/// name any derive we recognise.
+fn add_derive(source: &str, tree: &Tree, instruction: &str) -> Option<TextEdit> {
+    let item = first_named_child(tree.root_node())?;
+    if !matches!(item.kind(), "struct_item" | "enum_item") {
+        return None;
+    }
+    const KNOWN_DERIVES: &[&str] = &[
+        "Debug",
+        "Clone",
+        "Copy",
+        "PartialEq",
+        "Eq",
+        "Hash",
+        "Default",
+        "Serialize",
+        "Deserialize",
+    ];
+    let requested: Vec<&str> = KNOWN_DERIVES
+        .iter()
+        .copied()
+        .filter(|derive| {
+            instruction
+                .split(|c: char| !c.is_ascii_alphanumeric())
+                .any(|word| word == *derive)
+        })
+        .collect();
+    if requested.is_empty() {
+        return None;
+    }
+
+    let indent = leading_whitespace(source, item.start_byte());
+    let insert_at = item.start_byte() - indent.len();
+    Some(TextEdit::new(
+        insert_at..insert_at,
+        format!("{indent}#[derive({})]\n", requested.join(", ")),
+    ))
+}
+
+/// Splits a single `use a::{b, c};` into one `use` per imported name. Only
+/// matches a `use_declaration` wrapping a `use_list` - a plain `use a::b;`
+/// has nothing to split.
+fn split_import(source: &str, tree: &Tree) -> Option<TextEdit> {
+    let item = first_named_child(tree.root_node())?;
+    if item.kind() != "use_declaration" {
+        return None;
+    }
+    let use_clause = item.named_child(0)?;
+    let scoped = use_clause_with_list(source, use_clause)?;
+    let (prefix, list) = scoped;
+    let indent = leading_whitespace(source, item.start_byte());
+
+    let names: Vec<&str> = list
+        .named_children(&mut list.walk())
+        .map(|child| &source[child.start_byte()..child.end_byte()])
+        .collect();
+    if names.len() < 2 {
+        return None;
+    }
+
+    let expanded = names
+        .iter()
+        .map(|name| format!("{indent}use {prefix}::{name};"))
+        .collect::<Vec<_>>()
+        .join("\n");
+    Some(TextEdit::new(item.start_byte()..item.end_byte(), expanded))
+}
+
+/// Finds a `use_declaration`'s `scoped_use_list` and returns its path prefix
+/// (rendered back to source text) alongside the `{ ... }` list node.
+fn use_clause_with_list<'a>(source: &'a str, node: Node) -> Option<(&'a str, Node<'a>)> {
+    if node.kind() != "scoped_use_list" {
+        return None;
+    }
+    let path = node.child_by_field_name("path")?;
+    let list = node.child_by_field_name("list")?;
+    Some((&source[path.start_byte()..path.end_byte()], list))
+}
+
+/// Rewrites `if let PAT = EXPR { BODY } else { ELSE }` into the equivalent
+/// `match EXPR { PAT => { BODY } _ => { ELSE } }`. Only matches when an
+/// `else` branch is present, since `if let` without one has no fallback arm
+/// to give the match.
+fn replace_if_let_with_match(source: &str, tree: &Tree) -> Option<TextEdit> {
+    let statement = first_named_child(tree.root_node())?;
+    let item = if statement.kind() == "expression_statement" {
+        statement.named_child(0)?
+    } else {
+        statement
+    };
+    if item.kind() != "if_let_expression" {
+        return None;
+    }
+    let pattern = item.child_by_field_name("pattern")?;
+    let value = item.child_by_field_name("value")?;
+    let consequence = item.child_by_field_name("consequence")?;
+    let alternative = item.child_by_field_name("alternative")?;
+    let else_body = alternative.named_child(0)?;
+
+    let pattern_text = &source[pattern.start_byte()..pattern.end_byte()];
+    let value_text = &source[value.start_byte()..value.end_byte()];
+    let consequence_text = &source[consequence.start_byte()..consequence.end_byte()];
+    let else_text = &source[else_body.start_byte()..else_body.end_byte()];
+
+    let replacement = format!(
+        "match {value_text} {{\n    {pattern_text} => {consequence_text}\n    _ => {else_text},\n}}"
+    );
+    Some(TextEdit::new(item.start_byte()..item.end_byte(), replacement))
+}
+
+/// Extracts the selection's single expression statement into a `let`
+/// binding named from the instruction (falling back to `extracted`), then
+/// replaces the statement with a reference to that binding. Only matches
+/// when the selection is exactly one `expression_statement`.
+fn introduce_variable(source: &str, tree: &Tree, instruction: &str) -> Option<TextEdit> {
+    let root = tree.root_node();
+    if root.named_child_count() != 1 {
+        return None;
+    }
+    let statement = root.named_child(0)?;
+    if statement.kind() != "expression_statement" {
+        return None;
+    }
+    let expression = statement.named_child(0)?;
+    let expression_text = &source[expression.start_byte()..expression.end_byte()];
+
+    let name = variable_name_from_instruction(instruction).unwrap_or_else(|| "extracted".to_owned());
+    let indent = leading_whitespace(source, statement.start_byte());
+    let replacement = format!("{indent}let {name} = {expression_text};\n{indent}{name};");
+    Some(TextEdit::new(
+        statement.start_byte()..statement.end_byte(),
+        replacement,
+    ))
+}
+
+/// Pulls a `snake_case` identifier out of `instruction` if the user named
+/// one directly (e.g. "introduce a variable called `retry_count`"), rather
+/// than guessing one from the expression being extracted.
+fn variable_name_from_instruction(instruction: &str) -> Option<String> {
+    instruction
+        .split(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+        .find(|word| {
+            !word.is_empty()
+                && word.chars().next().is_some_and(|c| c.is_ascii_lowercase())
+                && word.contains('_')
+        })
+        .map(|word| word.to_owned())
+}
+
+fn first_named_child(root: Node) -> Option<Node> {
+    root.named_child(0)
+}
+
+fn leading_whitespace(source: &str, byte_offset: usize) -> String {
+    let before = &source[..byte_offset];
+    let line_start = before.rfind('\n').map(|idx| idx + 1).unwrap_or(0);
+    source[line_start..byte_offset]
+        .chars()
+        .take_while(|c| *c == ' ' || *c == '\t')
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(source: &str) -> Tree {
+        let mut parser = parser_for_language("rust").expect("rust grammar available");
+        parser.parse(source, None).expect("parses")
+    }
+
+    #[test]
+    fn test_change_visibility_adds_pub() {
+        let source = "struct Foo;";
+        let tree = parse(source);
+        let edit = change_visibility(source, &tree).expect("matches");
+        assert_eq!(edit.apply(source), "pub struct Foo;");
+    }
+
+    #[test]
+    fn test_change_visibility_removes_pub() {
+        let source = "pub struct Foo;";
+        let tree = parse(source);
+        let edit = change_visibility(source, &tree).expect("matches");
+        assert_eq!(edit.apply(source), "struct Foo;");
+    }
+
+    #[test]
+    fn test_add_derive_inserts_requested_derives() {
+        let source = "struct Foo {\n    bar: usize,\n}";
+        let tree = parse(source);
+        let edit =
+            add_derive(source, &tree, "derive Debug and Clone for this").expect("matches");
+        assert_eq!(
+            edit.apply(source),
+            "#[derive(Debug, Clone)]\nstruct Foo {\n    bar: usize,\n}"
+        );
+    }
+
+    #[test]
+    fn test_split_import_expands_use_list() {
+        let source = "use std::collections::{HashMap, HashSet};";
+        let tree = parse(source);
+        let edit = split_import(source, &tree).expect("matches");
+        assert_eq!(
+            edit.apply(source),
+            "use std::collections::HashMap;\nuse std::collections::HashSet;"
+        );
+    }
+
+    #[test]
+    fn test_replace_if_let_with_match() {
+        let source = "if let Some(x) = maybe_x { use_it(x) } else { fallback() }";
+        let tree = parse(source);
+        let edit = replace_if_let_with_match(source, &tree).expect("matches");
+        assert_eq!(
+            edit.apply(source),
+            "match maybe_x {\n    Some(x) => { use_it(x) }\n    _ => { fallback() },\n}"
+        );
+    }
+
+    #[test]
+    fn test_introduce_variable_extracts_named_binding() {
+        let source = "compute_total(items);";
+        let tree = parse(source);
+        let edit =
+            introduce_variable(source, &tree, "introduce a variable called total_count")
+                .expect("matches");
+        assert_eq!(
+            edit.apply(source),
+            "let total_count = compute_total(items);\ntotal_count;"
+        );
+    }
+}
@@ -0,0 +1,196 @@
+use llm_client::clients::types::{LLMClientCompletionRequest, LLMClientMessage};
+
+use crate::agentic::tool::code_edit::types::CodeEdit;
+
+/// The sentinel tokens a FIM (fill-in-the-middle) model expects its prompt
+/// wrapped in: `{prefix_token}{above}{suffix_token}{below}{middle_token}`,
+/// with the model generating only what goes where `{middle_token}` sits.
+/// Each native-FIM model family spells these differently, so this is kept
+/// per-model rather than hardcoded once.
+struct FimTemplate {
+    prefix_token: &'static str,
+    suffix_token: &'static str,
+    middle_token: &'static str,
+    // Codestral's sentinel order is suffix-then-prefix (`[SUFFIX]{suffix}[PREFIX]{prefix}`),
+    // the opposite of DeepSeek/StarCoder's prefix-then-suffix - this is what
+    // lets one prompt-building routine serve every template below.
+    suffix_before_prefix: bool,
+}
+
+/// The model families this formatter knows a native FIM template for.
+/// The broker's model -> formatter selection does not pick one of these; a
+/// caller chooses the model when constructing `FimCodeEditFormatter`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FimModel {
+    Codestral,
+    DeepSeekCoder,
+    StarCoder,
+}
+
+impl FimModel {
+    fn template(&self) -> FimTemplate {
+        match self {
+            // Mistral/Codestral: https://docs.mistral.ai/capabilities/code_generation/
+            FimModel::Codestral => FimTemplate {
+                prefix_token: "[PREFIX]",
+                suffix_token: "[SUFFIX]",
+                middle_token: "",
+                suffix_before_prefix: true,
+            },
+            FimModel::DeepSeekCoder => FimTemplate {
+                prefix_token: "<|fim▁begin|>",
+                suffix_token: "<|fim▁hole|>",
+                middle_token: "<|fim▁end|>",
+                suffix_before_prefix: false,
+            },
+            FimModel::StarCoder => FimTemplate {
+                prefix_token: "<fim_prefix>",
+                suffix_token: "<fim_suffix>",
+                middle_token: "<fim_middle>",
+                suffix_before_prefix: false,
+            },
+        }
+    }
+}
+
+/// Builds fill-in-the-middle completion prompts for models with native FIM
+/// support, as a short-circuit alternative to `CodeEditPromptFormatters`'
+/// `format_prompt`: instead of asking for a full rewrite of `<code_to_edit>`
+/// through a system+user chat exchange, it wraps the surrounding code in the
+/// model's own sentinel tokens and asks for just the missing middle span -
+/// faster, and it can't drift onto rewriting code outside the hole since
+/// there's nowhere in the prompt for that code to go.
+pub struct FimCodeEditFormatter {
+    model: FimModel,
+}
+
+impl FimCodeEditFormatter {
+    pub fn new(model: FimModel) -> Self {
+        Self { model }
+    }
+
+    /// Codestral/DeepSeek/StarCoder-style single-prompt completion: the
+    /// instruction (if any) goes in as a leading comment since these models
+    /// have no separate system-message channel, then `above_context` as the
+    /// prefix and `below_context` as the suffix around the fill sentinel.
+    pub fn format_fim_prompt(&self, context: &CodeEdit) -> LLMClientCompletionRequest {
+        let template = self.model.template();
+        let prefix = context.above_context().unwrap_or("");
+        let suffix = context.below_context().unwrap_or("");
+
+        let mut prompt = String::new();
+        let instruction = context.instruction();
+        if !instruction.is_empty() {
+            prompt.push_str(&comment_out(instruction, context.language()));
+            prompt.push('\n');
+        }
+        if template.suffix_before_prefix {
+            prompt.push_str(template.suffix_token);
+            prompt.push_str(suffix);
+            prompt.push_str(template.prefix_token);
+            prompt.push_str(prefix);
+        } else {
+            prompt.push_str(template.prefix_token);
+            prompt.push_str(prefix);
+            prompt.push_str(template.suffix_token);
+            prompt.push_str(suffix);
+        }
+        prompt.push_str(template.middle_token);
+
+        LLMClientCompletionRequest::new(
+            context.model().clone(),
+            vec![LLMClientMessage::user(prompt)],
+            0.2,
+            None,
+        )
+    }
+}
+
+/// Renders `instruction` as a line comment in `language`, falling back to
+/// `//` for anything not in this short list - a best-effort hint for models
+/// with no dedicated instruction channel, not a correctness requirement.
+fn comment_out(instruction: &str, language: &str) -> String {
+    let marker = match language {
+        "python" | "ruby" | "bash" | "shell" => "#",
+        "html" | "xml" => "<!--",
+        _ => "//",
+    };
+    if marker == "<!--" {
+        format!("<!-- {instruction} -->")
+    } else {
+        format!("{marker} {instruction}")
+    }
+}
+
+/// Replays `format_fim_prompt`'s own token-assembly logic against a
+/// `FimTemplate` directly, so these tests pin down the sentinel order/tokens
+/// for each model without needing a `CodeEdit` to drive the public method.
+fn render_fim_body(template: &FimTemplate, prefix: &str, suffix: &str) -> String {
+    let mut prompt = String::new();
+    if template.suffix_before_prefix {
+        prompt.push_str(template.suffix_token);
+        prompt.push_str(suffix);
+        prompt.push_str(template.prefix_token);
+        prompt.push_str(prefix);
+    } else {
+        prompt.push_str(template.prefix_token);
+        prompt.push_str(prefix);
+        prompt.push_str(template.suffix_token);
+        prompt.push_str(suffix);
+    }
+    prompt.push_str(template.middle_token);
+    prompt
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_codestral_template_puts_suffix_before_prefix() {
+        let template = FimModel::Codestral.template();
+        assert!(template.suffix_before_prefix);
+        assert_eq!(template.prefix_token, "[PREFIX]");
+        assert_eq!(template.suffix_token, "[SUFFIX]");
+        assert_eq!(template.middle_token, "");
+        assert_eq!(
+            render_fim_body(&template, "before_the_hole", "after_the_hole"),
+            "[SUFFIX]after_the_hole[PREFIX]before_the_hole"
+        );
+    }
+
+    #[test]
+    fn test_deepseek_coder_template_puts_prefix_before_suffix() {
+        let template = FimModel::DeepSeekCoder.template();
+        assert!(!template.suffix_before_prefix);
+        assert_eq!(
+            render_fim_body(&template, "before_the_hole", "after_the_hole"),
+            "<|fim▁begin|>before_the_hole<|fim▁hole|>after_the_hole<|fim▁end|>"
+        );
+    }
+
+    #[test]
+    fn test_star_coder_template_puts_prefix_before_suffix() {
+        let template = FimModel::StarCoder.template();
+        assert!(!template.suffix_before_prefix);
+        assert_eq!(
+            render_fim_body(&template, "before_the_hole", "after_the_hole"),
+            "<fim_prefix>before_the_hole<fim_suffix>after_the_hole<fim_middle>"
+        );
+    }
+
+    #[test]
+    fn test_comment_out_uses_hash_for_python() {
+        assert_eq!(comment_out("do a thing", "python"), "# do a thing");
+    }
+
+    #[test]
+    fn test_comment_out_uses_html_comment_markers() {
+        assert_eq!(comment_out("do a thing", "html"), "<!-- do a thing -->");
+    }
+
+    #[test]
+    fn test_comment_out_falls_back_to_double_slash() {
+        assert_eq!(comment_out("do a thing", "rust"), "// do a thing");
+    }
+}
@@ -1,17 +1,55 @@
 use std::sync::Arc;
 
-use llm_client::broker::LLMBroker;
+use llm_client::{
+    broker::LLMBroker,
+    clients::types::{LLMClientCompletionRequest, LLMClientMessage, LLMClientTool},
+};
 
 use async_trait::async_trait;
+use serde_json::json;
 
 use crate::agentic::{
     symbol::identifier::LLMProperties,
     tool::{
-        code_symbol::{important::CodeSymbolImportantResponse, types::CodeSymbolError},
+        code_symbol::{
+            important::{CodeSymbolImportantResponse, CodeSymbolWithThinking},
+            types::CodeSymbolError,
+        },
         search::types::{BigSearch, BigSearchRequest},
     },
 };
 
+/// How many propose -> retrieve -> rank rounds we're willing to spend before
+/// settling for whatever has survived ranking so far, even if the model
+/// never calls `rank_snippets` with `satisfied: true`.
+const MAX_SEARCH_ROUNDS: usize = 3;
+
+/// Rough ceiling on how much snippet text we feed back to the model in a
+/// single round. Once a round's candidates would blow through this, the
+/// least-relevant ones are dropped first rather than truncating every
+/// snippet uniformly - "least-relevant" being whatever the previous round's
+/// `rank_snippets` call put last, or retrieval order on the first round.
+const SNIPPET_CHAR_BUDGET: usize = 40_000;
+
+/// A candidate the model wants retrieved, before we've gone and fetched the
+/// snippet for it.
+#[derive(Debug, Clone)]
+struct CandidateSymbol {
+    fs_file_path: String,
+    symbol_name: String,
+    #[allow(dead_code)]
+    reasoning: String,
+}
+
+/// A candidate once we have its snippet attached, carried across rounds so
+/// ranking and pruning has something to work with.
+#[derive(Debug, Clone)]
+struct RetrievedSnippet {
+    fs_file_path: String,
+    symbol_name: String,
+    content: String,
+}
+
 pub struct GoogleStudioBigSearch {
     llm_client: Arc<LLMBroker>,
     fail_over_llm: LLMProperties,
@@ -24,14 +62,405 @@ impl GoogleStudioBigSearch {
             fail_over_llm,
         }
     }
+
+    /// Round one's tool: without having seen any code yet, propose candidate
+    /// (file, symbol) pairs worth retrieving for the user's query.
+    fn propose_candidates_tool() -> LLMClientTool {
+        LLMClientTool::new(
+            "propose_candidates",
+            "Propose candidate files and symbols likely relevant to the user's query.",
+            json!({
+                "type": "object",
+                "properties": {
+                    "candidates": {
+                        "type": "array",
+                        "description": "Candidate (file, symbol) pairs to retrieve and inspect, most promising first.",
+                        "items": {
+                            "type": "object",
+                            "properties": {
+                                "fs_file_path": {
+                                    "type": "string",
+                                    "description": "Path of the file the symbol lives in."
+                                },
+                                "symbol_name": {
+                                    "type": "string",
+                                    "description": "Name of the function, type, or symbol to inspect."
+                                },
+                                "reasoning": {
+                                    "type": "string",
+                                    "description": "Why this symbol is likely relevant to the query."
+                                }
+                            },
+                            "required": ["fs_file_path", "symbol_name", "reasoning"]
+                        }
+                    }
+                },
+                "required": ["candidates"]
+            }),
+        )
+    }
+
+    /// Later rounds' tool: given the snippets retrieved so far, say which
+    /// ones are actually relevant and need editing, and whether that's
+    /// enough to stop searching.
+    fn rank_snippets_tool() -> LLMClientTool {
+        LLMClientTool::new(
+            "rank_snippets",
+            "Mark which retrieved snippets are relevant and need editing, pruning the rest.",
+            json!({
+                "type": "object",
+                "properties": {
+                    "relevant_symbols": {
+                        "type": "array",
+                        "description": "(fs_file_path, symbol_name) pair of every snippet that is relevant and should be kept, most important first.",
+                        "items": {
+                            "type": "object",
+                            "properties": {
+                                "fs_file_path": {
+                                    "type": "string",
+                                    "description": "Path of the file the relevant snippet lives in."
+                                },
+                                "symbol_name": {
+                                    "type": "string",
+                                    "description": "Name of the relevant symbol."
+                                }
+                            },
+                            "required": ["fs_file_path", "symbol_name"]
+                        }
+                    },
+                    "satisfied": {
+                        "type": "boolean",
+                        "description": "True if the kept snippets are enough to answer the query and no further search round is needed."
+                    }
+                },
+                "required": ["relevant_symbols", "satisfied"]
+            }),
+        )
+    }
+
+    fn propose_candidates_system_message() -> String {
+        "You are an expert software engineer exploring an unfamiliar, large codebase to find the symbols relevant to a user's query.
+You do not have the file contents yet - only your general knowledge of how codebases like this tend to be organised.
+Think step by step about which files and symbols are likely to be relevant, then call the `propose_candidates` tool with your best guesses, most promising first.".to_owned()
+    }
+
+    fn rank_snippets_system_message() -> String {
+        "You are an expert software engineer reviewing code snippets retrieved for a user's query.
+Some of the retrieved snippets will be irrelevant noise; discard those. Think step by step about which snippets actually matter, then call the `rank_snippets` tool with the relevant ones and whether you're satisfied or need another round of search.".to_owned()
+    }
+
+    /// Formats retrieved snippets the same way `AnthropicCodeEditFromatter::find_code_section`
+    /// formats a file's sections - `<id>`/`<content>` pairs the model can refer back to by id.
+    fn format_snippets(snippets: &[RetrievedSnippet]) -> String {
+        snippets
+            .iter()
+            .enumerate()
+            .map(|(idx, snippet)| {
+                let fs_file_path = &snippet.fs_file_path;
+                let symbol_name = &snippet.symbol_name;
+                let content = &snippet.content;
+                format!(
+                    r#"<section>
+<id>
+{idx}
+</id>
+<file_path>{fs_file_path}</file_path>
+<symbol_name>{symbol_name}</symbol_name>
+<content>
+{content}
+</content>
+</section>"#
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    /// Drops the tail of `snippets` (the least-relevant end, per the caller's
+    /// ordering) until the remaining content fits `SNIPPET_CHAR_BUDGET`,
+    /// rather than truncating every snippet uniformly.
+    fn apply_token_budget(mut snippets: Vec<RetrievedSnippet>) -> Vec<RetrievedSnippet> {
+        let mut total: usize = snippets.iter().map(|s| s.content.len()).sum();
+        while total > SNIPPET_CHAR_BUDGET && !snippets.is_empty() {
+            if let Some(dropped) = snippets.pop() {
+                total = total.saturating_sub(dropped.content.len());
+            }
+        }
+        snippets
+    }
+
+    async fn propose_candidates(
+        &self,
+        input: &BigSearchRequest,
+    ) -> Result<Vec<CandidateSymbol>, CodeSymbolError> {
+        let user_message = format!(
+            r#"<user_query>
+{}
+</user_query>"#,
+            input.user_query()
+        );
+        let request = LLMClientCompletionRequest::new(
+            input.llm().clone(),
+            vec![
+                LLMClientMessage::system(Self::propose_candidates_system_message()),
+                LLMClientMessage::user(user_message),
+            ],
+            0.2,
+            None,
+        )
+        .set_tools(vec![Self::propose_candidates_tool()]);
+
+        let tool_input = self
+            .llm_client
+            .stream_function_call(request, self.fail_over_llm.clone())
+            .await?;
+
+        let candidates = tool_input["candidates"]
+            .as_array()
+            .cloned()
+            .unwrap_or_default()
+            .into_iter()
+            .filter_map(|candidate| {
+                Some(CandidateSymbol {
+                    fs_file_path: candidate["fs_file_path"].as_str()?.to_owned(),
+                    symbol_name: candidate["symbol_name"].as_str()?.to_owned(),
+                    reasoning: candidate["reasoning"].as_str().unwrap_or("").to_owned(),
+                })
+            })
+            .collect();
+        Ok(candidates)
+    }
+
+    /// Asks the model to prune `snippets` down to what's actually relevant,
+    /// and whether another round of `propose_candidates` is worth running.
+    async fn rank_snippets(
+        &self,
+        input: &BigSearchRequest,
+        snippets: Vec<RetrievedSnippet>,
+    ) -> Result<(Vec<RetrievedSnippet>, bool), CodeSymbolError> {
+        let budgeted = Self::apply_token_budget(snippets);
+        let user_message = format!(
+            r#"<user_query>
+{}
+</user_query>
+
+<retrieved_snippets>
+{}
+</retrieved_snippets>"#,
+            input.user_query(),
+            Self::format_snippets(&budgeted)
+        );
+        let request = LLMClientCompletionRequest::new(
+            input.llm().clone(),
+            vec![
+                LLMClientMessage::system(Self::rank_snippets_system_message()),
+                LLMClientMessage::user(user_message),
+            ],
+            0.2,
+            None,
+        )
+        .set_tools(vec![Self::rank_snippets_tool()]);
+
+        let tool_input = self
+            .llm_client
+            .stream_function_call(request, self.fail_over_llm.clone())
+            .await?;
+
+        let relevant_symbols: Vec<(String, String)> = tool_input["relevant_symbols"]
+            .as_array()
+            .cloned()
+            .unwrap_or_default()
+            .into_iter()
+            .filter_map(|value| {
+                let fs_file_path = value["fs_file_path"].as_str()?.to_owned();
+                let symbol_name = value["symbol_name"].as_str()?.to_owned();
+                Some((fs_file_path, symbol_name))
+            })
+            .collect();
+        let satisfied = tool_input["satisfied"].as_bool().unwrap_or(false);
+
+        let kept = Self::filter_relevant(budgeted, &relevant_symbols);
+        Ok((kept, satisfied))
+    }
+
+    /// Keeps only the snippets whose `(fs_file_path, symbol_name)` pair
+    /// appears in `relevant_symbols`, the model's pick of what's actually
+    /// worth keeping this round, in the order `relevant_symbols` lists them
+    /// so the least relevant end up at the tail.
+    fn filter_relevant(
+        mut snippets: Vec<RetrievedSnippet>,
+        relevant_symbols: &[(String, String)],
+    ) -> Vec<RetrievedSnippet> {
+        let mut kept = Vec::new();
+        for (fs_file_path, symbol_name) in relevant_symbols {
+            if let Some(position) = snippets.iter().position(|snippet| {
+                fs_file_path == &snippet.fs_file_path && symbol_name == &snippet.symbol_name
+            }) {
+                kept.push(snippets.remove(position));
+            }
+        }
+        kept
+    }
+
+    /// Drops any `candidates` whose `(fs_file_path, symbol_name)` pair is
+    /// already present in `already_retrieved`, so a later round's
+    /// `propose_candidates` call can't re-fetch and re-append a snippet the
+    /// loop already has.
+    fn dedupe_against_seen(
+        candidates: Vec<CandidateSymbol>,
+        already_retrieved: &[RetrievedSnippet],
+    ) -> Vec<CandidateSymbol> {
+        candidates
+            .into_iter()
+            .filter(|candidate| {
+                !already_retrieved.iter().any(|snippet| {
+                    snippet.fs_file_path == candidate.fs_file_path
+                        && snippet.symbol_name == candidate.symbol_name
+                })
+            })
+            .collect()
+    }
 }
 
 #[async_trait]
 impl BigSearch for GoogleStudioBigSearch {
+    /// Turns `input` into a `CodeSymbolImportantResponse` through a bounded
+    /// propose -> retrieve -> rank loop: the first round asks Gemini for
+    /// candidate files/symbols with no code in front of it yet, every round
+    /// after that feeds the retrieved snippets back and asks it to prune to
+    /// what's relevant and say whether it's satisfied. We stop on whichever
+    /// comes first, `satisfied: true` or `MAX_SEARCH_ROUNDS` rounds, and
+    /// report whatever survived ranking either way.
     async fn search(
         &self,
         input: BigSearchRequest,
     ) -> Result<CodeSymbolImportantResponse, CodeSymbolError> {
-        todo!();
+        let candidates = self.propose_candidates(&input).await?;
+
+        let mut snippets: Vec<RetrievedSnippet> = candidates
+            .into_iter()
+            .filter_map(|candidate| {
+                let content = input.fetch_symbol(&candidate.fs_file_path, &candidate.symbol_name)?;
+                Some(RetrievedSnippet {
+                    fs_file_path: candidate.fs_file_path,
+                    symbol_name: candidate.symbol_name,
+                    content,
+                })
+            })
+            .collect();
+
+        for round in 0..MAX_SEARCH_ROUNDS {
+            let (ranked, satisfied) = self.rank_snippets(&input, snippets).await?;
+            snippets = ranked;
+            if satisfied || round + 1 == MAX_SEARCH_ROUNDS {
+                break;
+            }
+
+            let more_candidates = self.propose_candidates(&input).await?;
+            let new_candidates = Self::dedupe_against_seen(more_candidates, &snippets);
+            let new_snippets = new_candidates.into_iter().filter_map(|candidate| {
+                let content = input.fetch_symbol(&candidate.fs_file_path, &candidate.symbol_name)?;
+                Some(RetrievedSnippet {
+                    fs_file_path: candidate.fs_file_path,
+                    symbol_name: candidate.symbol_name,
+                    content,
+                })
+            });
+            snippets.extend(new_snippets);
+        }
+
+        let symbols = snippets
+            .into_iter()
+            .map(|snippet| {
+                CodeSymbolWithThinking::new(
+                    snippet.symbol_name,
+                    snippet.content,
+                    snippet.fs_file_path,
+                )
+            })
+            .collect();
+        Ok(CodeSymbolImportantResponse::new(symbols, vec![]))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CandidateSymbol, GoogleStudioBigSearch, RetrievedSnippet, SNIPPET_CHAR_BUDGET};
+
+    fn snippet(fs_file_path: &str, symbol_name: &str, content: &str) -> RetrievedSnippet {
+        RetrievedSnippet {
+            fs_file_path: fs_file_path.to_owned(),
+            symbol_name: symbol_name.to_owned(),
+            content: content.to_owned(),
+        }
+    }
+
+    fn candidate(fs_file_path: &str, symbol_name: &str) -> CandidateSymbol {
+        CandidateSymbol {
+            fs_file_path: fs_file_path.to_owned(),
+            symbol_name: symbol_name.to_owned(),
+            reasoning: "because".to_owned(),
+        }
+    }
+
+    #[test]
+    fn test_apply_token_budget_keeps_everything_under_the_budget() {
+        let snippets = vec![
+            snippet("a.rs", "one", "x".repeat(10).as_str()),
+            snippet("b.rs", "two", "y".repeat(10).as_str()),
+        ];
+        let kept = GoogleStudioBigSearch::apply_token_budget(snippets);
+        assert_eq!(kept.len(), 2);
+    }
+
+    #[test]
+    fn test_apply_token_budget_drops_the_tail_until_it_fits() {
+        let snippets = vec![
+            snippet("a.rs", "one", "x".repeat(SNIPPET_CHAR_BUDGET - 10).as_str()),
+            snippet("b.rs", "two", "y".repeat(SNIPPET_CHAR_BUDGET - 10).as_str()),
+            snippet("c.rs", "three", "z".repeat(SNIPPET_CHAR_BUDGET - 10).as_str()),
+        ];
+        let kept = GoogleStudioBigSearch::apply_token_budget(snippets);
+        assert_eq!(kept.len(), 1);
+        assert_eq!(kept[0].fs_file_path, "a.rs");
+    }
+
+    #[test]
+    fn test_filter_relevant_keeps_only_snippets_the_model_marked_relevant() {
+        let snippets = vec![
+            snippet("a.rs", "one", "content_a"),
+            snippet("b.rs", "two", "content_b"),
+        ];
+        let relevant_symbols = vec![("a.rs".to_owned(), "one".to_owned())];
+        let kept = GoogleStudioBigSearch::filter_relevant(snippets, &relevant_symbols);
+        assert_eq!(kept.len(), 1);
+        assert_eq!(kept[0].fs_file_path, "a.rs");
+        assert_eq!(kept[0].symbol_name, "one");
+    }
+
+    #[test]
+    fn test_filter_relevant_does_not_match_across_different_files() {
+        let snippets = vec![snippet("a.rs", "one", "content_a")];
+        let relevant_symbols = vec![("b.rs".to_owned(), "one".to_owned())];
+        let kept = GoogleStudioBigSearch::filter_relevant(snippets, &relevant_symbols);
+        assert!(kept.is_empty());
+    }
+
+    #[test]
+    fn test_dedupe_against_seen_drops_candidates_already_retrieved() {
+        let candidates = vec![candidate("a.rs", "one"), candidate("b.rs", "two")];
+        let already_retrieved = vec![snippet("a.rs", "one", "content_a")];
+        let deduped = GoogleStudioBigSearch::dedupe_against_seen(candidates, &already_retrieved);
+        assert_eq!(deduped.len(), 1);
+        assert_eq!(deduped[0].fs_file_path, "b.rs");
+        assert_eq!(deduped[0].symbol_name, "two");
+    }
+
+    #[test]
+    fn test_dedupe_against_seen_keeps_candidates_with_no_overlap() {
+        let candidates = vec![candidate("a.rs", "one")];
+        let already_retrieved = vec![snippet("b.rs", "two", "content_b")];
+        let deduped = GoogleStudioBigSearch::dedupe_against_seen(candidates, &already_retrieved);
+        assert_eq!(deduped.len(), 1);
     }
 }
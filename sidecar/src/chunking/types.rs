@@ -1,7 +1,152 @@
-use super::text_document::Range;
+use std::collections::HashMap;
+
+use super::text_document::{Position, Range};
 
 /// Some common types which can be reused across calls
 
+/// A resolved (or attempted) intra-doc link found inside a documentation
+/// block, e.g. rustdoc's `` [`Foo`] ``/`[text](Foo::bar)`, Javadoc's
+/// `{@link Foo#bar}`, or a plain Markdown `[text](symbol)`. `resolved_range`
+/// is `None` when `target_name` doesn't match any symbol known in the same
+/// file - the link text is preserved either way rather than dropped.
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct DocumentationLink {
+    pub link_text: String,
+    pub target_name: String,
+    pub resolved_range: Option<Range>,
+}
+
+/// Parses intra-doc link syntax out of `documentation` and resolves each
+/// target against `known_symbols` (symbol name -> its range in this file).
+pub fn resolve_documentation_links(
+    documentation: &str,
+    known_symbols: &HashMap<String, Range>,
+) -> Vec<DocumentationLink> {
+    let mut links = resolve_bracket_links(documentation, known_symbols);
+    links.extend(resolve_javadoc_links(documentation, known_symbols));
+    links
+}
+
+/// Matches rustdoc/Markdown style links: `` [`Type`] ``, `[Type]`, and
+/// `[text](Type::method)`.
+fn resolve_bracket_links(
+    documentation: &str,
+    known_symbols: &HashMap<String, Range>,
+) -> Vec<DocumentationLink> {
+    let mut links = Vec::new();
+    let mut rest = documentation;
+    while let Some(open) = rest.find('[') {
+        let after_open = &rest[open + 1..];
+        let Some(close) = after_open.find(']') else {
+            break;
+        };
+        let link_text = after_open[..close].trim_matches('`').to_owned();
+        let after_close = &after_open[close + 1..];
+        let (target_name, trailing_consumed) = if after_close.starts_with('(') {
+            match after_close.find(')') {
+                Some(paren_close) => (after_close[1..paren_close].to_owned(), paren_close + 1),
+                None => (link_text.clone(), 0),
+            }
+        } else {
+            (link_text.clone(), 0)
+        };
+        links.push(make_documentation_link(link_text, target_name, known_symbols));
+        rest = &after_close[trailing_consumed..];
+    }
+    links
+}
+
+/// Matches Javadoc style links: `{@link Type#method}` / `{@link Type}`.
+fn resolve_javadoc_links(
+    documentation: &str,
+    known_symbols: &HashMap<String, Range>,
+) -> Vec<DocumentationLink> {
+    let mut links = Vec::new();
+    let mut rest = documentation;
+    while let Some(open) = rest.find("{@link") {
+        let after_open = &rest[open + "{@link".len()..];
+        let Some(close) = after_open.find('}') else {
+            break;
+        };
+        let raw_target = after_open[..close].trim();
+        // javadoc uses `Type#member`, normalise to `Type::member` so it
+        // shares the same symbol-name space as rustdoc-style targets
+        let target_name = raw_target.replace('#', "::");
+        links.push(make_documentation_link(
+            raw_target.to_owned(),
+            target_name,
+            known_symbols,
+        ));
+        rest = &after_open[close + 1..];
+    }
+    links
+}
+
+fn make_documentation_link(
+    link_text: String,
+    target_name: String,
+    known_symbols: &HashMap<String, Range>,
+) -> DocumentationLink {
+    let resolved_range = known_symbols.get(&target_name).cloned().or_else(|| {
+        // a `Type::method` style target might only have its last segment
+        // (`method`) registered on its own; the bare `Type` is not tried
+        target_name
+            .rsplit("::")
+            .next()
+            .and_then(|leaf| known_symbols.get(leaf))
+            .cloned()
+    });
+    DocumentationLink {
+        link_text,
+        target_name,
+        resolved_range,
+    }
+}
+
+/// A block which can carry resolved documentation links alongside its plain
+/// documentation text.
+pub trait HasDocumentationLinks {
+    fn documentation_text(&self) -> Option<&str>;
+    fn set_documentation_links(&mut self, links: Vec<DocumentationLink>);
+}
+
+/// Runs [`resolve_documentation_links`] over every block's documentation (if
+/// any) and stores the result back onto the block.
+pub fn attach_documentation_links<T: HasDocumentationLinks>(
+    blocks: &mut [T],
+    known_symbols: &HashMap<String, Range>,
+) {
+    for block in blocks.iter_mut() {
+        if let Some(documentation) = block.documentation_text() {
+            let links = resolve_documentation_links(documentation, known_symbols);
+            block.set_documentation_links(links);
+        }
+    }
+}
+
+/// Gathers the symbol names declared in this file (from the flat
+/// `ClassInformation`/`TypeInformation`/`FunctionInformation` lists) so
+/// intra-doc links can be resolved against them.
+pub fn collect_known_symbol_names(
+    classes: &[ClassInformation],
+    types: &[TypeInformation],
+    functions: &[FunctionInformation],
+) -> HashMap<String, Range> {
+    let mut known_symbols = HashMap::new();
+    for class in classes {
+        known_symbols.insert(class.get_name().to_owned(), class.range().clone());
+    }
+    for type_information in types {
+        known_symbols.insert(type_information.get_name().to_owned(), type_information.range().clone());
+    }
+    for function in functions {
+        if let Some(name) = function.name() {
+            known_symbols.insert(name.to_owned(), function.range().clone());
+        }
+    }
+    known_symbols
+}
+
 #[derive(Debug, Default, Clone, serde::Serialize, serde::Deserialize)]
 pub struct FunctionNodeInformation {
     name: String,
@@ -9,6 +154,8 @@ pub struct FunctionNodeInformation {
     body: String,
     return_type: String,
     documentation: Option<String>,
+    documentation_links: Vec<DocumentationLink>,
+    decorators: Option<String>,
     variables: Vec<(String, Range)>,
 }
 
@@ -37,6 +184,10 @@ impl FunctionNodeInformation {
         self.documentation = Some(documentation);
     }
 
+    pub fn set_decorators(&mut self, decorators: String) {
+        self.decorators = Some(decorators);
+    }
+
     pub fn get_name(&self) -> &str {
         &self.name
     }
@@ -52,6 +203,18 @@ impl FunctionNodeInformation {
     pub fn get_documentation(&self) -> Option<&str> {
         self.documentation.as_deref()
     }
+
+    pub fn get_decorators(&self) -> Option<&str> {
+        self.decorators.as_deref()
+    }
+
+    pub fn set_documentation_links(&mut self, links: Vec<DocumentationLink>) {
+        self.documentation_links = links;
+    }
+
+    pub fn get_documentation_links(&self) -> &[DocumentationLink] {
+        &self.documentation_links
+    }
 }
 
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize, PartialEq, Eq, Hash)]
@@ -66,6 +229,20 @@ pub enum OutlineNodeType {
     FunctionName,
     // the body of the function
     FunctionBody,
+    // an enum declaration
+    Enum,
+    // a single variant inside an enum
+    EnumVariant,
+    // an interface declaration (TS, Java, Go)
+    Interface,
+    // a trait declaration (Rust)
+    Trait,
+    // a struct declaration
+    Struct,
+    // a type alias
+    TypeAlias,
+    // a field or property on a class/struct
+    Field,
 }
 
 impl OutlineNodeType {
@@ -76,12 +253,28 @@ impl OutlineNodeType {
             "definition.function" | "definition.method" => Some(Self::Function),
             "function.name" => Some(Self::FunctionName),
             "function.body" => Some(Self::FunctionBody),
+            "definition.enum" => Some(Self::Enum),
+            "definition.enum_variant" => Some(Self::EnumVariant),
+            "definition.interface" => Some(Self::Interface),
+            "definition.trait" => Some(Self::Trait),
+            "definition.struct" => Some(Self::Struct),
+            "definition.type_alias" => Some(Self::TypeAlias),
+            "definition.field" => Some(Self::Field),
             _ => None,
         }
     }
+
+    /// Whether this node kind can meaningfully hold children (methods inside
+    /// a class, variants inside an enum, ...).
+    pub fn is_container(&self) -> bool {
+        matches!(
+            self,
+            Self::Class | Self::Enum | Self::Interface | Self::Trait | Self::Struct
+        )
+    }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub struct OutlineNodeContent {
     range: Range,
     name: String,
@@ -104,7 +297,7 @@ impl OutlineNodeContent {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub struct OutlineNode {
     content: OutlineNodeContent,
     children: Vec<OutlineNodeContent>,
@@ -140,38 +333,349 @@ impl OutlineNode {
         // we want to generate the outline for the node here, we have to do some
         // language specific gating here but thats fine
         match &self.content.r#type {
-            OutlineNodeType::Class => {
-                if self.children.is_empty() {
-                    Some(self.content.content.to_owned())
-                } else {
-                    // for rust we have a special case here as we might have functions
-                    // inside which we want to show but its part of the implementation
-                    if &self.language == "rust" {
-                        // this is 100% a implementation unless over here, so lets use
-                        // it as such
-                        let implementation_name = self.content.name.to_owned();
-                        let children_content = self
-                            .children
-                            .iter()
-                            .map(|children| children.content.to_owned())
-                            .collect::<Vec<_>>()
-                            .join("\n");
-                        Some(format!(
-                            "impl {implementation_name} {{\n{children_content}\n}}"
-                        ))
-                    } else {
-                        // TODO(skcd): We will figure out support for other languages
-                        None
-                    }
+            OutlineNodeType::Class => Some(self.outline_for_class()),
+            OutlineNodeType::Function => Some(Self::signature_line(&self.language, &self.content.content)),
+            _ => None,
+        }
+    }
+
+    fn outline_for_class(&self) -> String {
+        if self.children.is_empty() {
+            return self.content.content.to_owned();
+        }
+        let class_name = self.content.name.to_owned();
+        let child_signatures = self
+            .children
+            .iter()
+            .map(|child| format!("    {}", Self::signature_line(&self.language, &child.content)))
+            .collect::<Vec<_>>()
+            .join("\n");
+        match self.language.as_str() {
+            // an `impl` block is the closest stand-in for "this class's methods"
+            // in rust, since there is no single class keyword
+            "rust" => format!("impl {class_name} {{\n{child_signatures}\n}}"),
+            "python" => format!("class {class_name}:\n{child_signatures}"),
+            "golang" | "go" => format!("type {class_name} struct {{\n{child_signatures}\n}}"),
+            // javascript/typescript/java/c-family all share the brace-delimited
+            // class shape, so one arm covers them
+            "javascript" | "typescript" | "javascriptreact" | "typescriptreact" | "java"
+            | "c" | "cpp" => format!("class {class_name} {{\n{child_signatures}\n}}"),
+            _ => format!("{class_name} {{\n{child_signatures}\n}}"),
+        }
+    }
+
+    /// Collapses a symbol's full source text down to its signature line,
+    /// eliding the body, using the language's body-start marker (`:` for
+    /// python, `{` everywhere else we support today).
+    fn signature_line(language: &str, content: &str) -> String {
+        let trimmed = content.trim();
+        let body_start = match language {
+            "python" => Self::top_level_colon(trimmed),
+            _ => trimmed.find('{'),
+        };
+        match body_start {
+            Some(index) => {
+                let head = trimmed[..index].trim_end();
+                match language {
+                    "python" => format!("{head}: ..."),
+                    _ => format!("{head} {{ ... }}"),
                 }
             }
-            OutlineNodeType::Function => None,
-            _ => None,
+            None => trimmed.to_owned(),
+        }
+    }
+
+    /// Finds the `:` that opens a `def`/`class` body, skipping over any `:`
+    /// nested inside `(...)` or `[...]` - e.g. parameter annotations
+    /// (`x: int`), default values, or subscripted return types
+    /// (`-> Dict[str, int]`) - so an annotated signature isn't truncated at
+    /// its first colon.
+    fn top_level_colon(signature: &str) -> Option<usize> {
+        let mut depth = 0i32;
+        for (index, ch) in signature.char_indices() {
+            match ch {
+                '(' | '[' => depth += 1,
+                ')' | ']' => depth -= 1,
+                ':' if depth <= 0 => return Some(index),
+                _ => {}
+            }
+        }
+        None
+    }
+}
+
+/// A node exposing a byte range, so the folding/documentation-attachment
+/// routines below can be written once and reused by every symbol type
+/// instead of being copy-pasted per struct.
+pub trait RangeContainer {
+    fn symbol_range(&self) -> &Range;
+}
+
+impl<T: RangeContainer> RangeContainer for &T {
+    fn symbol_range(&self) -> &Range {
+        (**self).symbol_range()
+    }
+}
+
+/// An augmented interval index built once over a slice of blocks sorted by
+/// `start_byte`: alongside each block's own range it keeps a running
+/// `max_end_byte` prefix, so `enclosing_index` can binary-search for the
+/// closest candidate and then walk left only as far as that prefix says is
+/// still worth checking, instead of scanning every block. Building the index
+/// is O(n), so lookups only get cheaper when one index serves many queries:
+/// `add_identifier_nodes` builds one for all of its identifiers, while
+/// `find_function_in_byte_offset` builds a fresh index on every call.
+pub struct RangeIndex {
+    // (start_byte, end_byte), sorted ascending by start_byte
+    ranges: Vec<(usize, usize)>,
+    max_end_prefix: Vec<usize>,
+}
+
+impl RangeIndex {
+    /// `items` must already be sorted by `start_byte` ascending.
+    pub fn build<T: RangeContainer>(items: &[T]) -> Self {
+        let mut running_max = 0;
+        let mut ranges = Vec::with_capacity(items.len());
+        let mut max_end_prefix = Vec::with_capacity(items.len());
+        for item in items {
+            let start = item.symbol_range().start_byte();
+            let end = item.symbol_range().end_byte();
+            running_max = running_max.max(end);
+            ranges.push((start, end));
+            max_end_prefix.push(running_max);
+        }
+        Self {
+            ranges,
+            max_end_prefix,
+        }
+    }
+
+    /// The index of the innermost block enclosing `offset`, matching the
+    /// largest start-byte on ties - identical to scanning forward over the
+    /// sorted blocks and keeping the last match.
+    pub fn enclosing_index(&self, offset: usize) -> Option<usize> {
+        // binary search for the last block whose start_byte <= offset
+        let mut low = 0usize;
+        let mut high = self.ranges.len();
+        while low < high {
+            let mid = low + (high - low) / 2;
+            if self.ranges[mid].0 <= offset {
+                low = mid + 1;
+            } else {
+                high = mid;
+            }
+        }
+        if low == 0 {
+            return None;
+        }
+        let mut index = low - 1;
+        loop {
+            if self.max_end_prefix[index] < offset {
+                return None;
+            }
+            if self.ranges[index].1 >= offset {
+                return Some(index);
+            }
+            if index == 0 {
+                return None;
+            }
+            index -= 1;
+        }
+    }
+
+    /// The indices of every block fully containing `range`.
+    pub fn contained_in<'a>(&'a self, range: &'a Range) -> impl Iterator<Item = usize> + 'a {
+        self.ranges
+            .iter()
+            .enumerate()
+            .filter(move |(_, (start, end))| {
+                *start <= range.start_byte() && *end >= range.end_byte()
+            })
+            .map(|(index, _)| index)
+    }
+}
+
+/// A [`RangeContainer`] that can also have a documentation block attached to
+/// it, absorbing the block's start position into its own range.
+pub trait DocumentedRangeContainer: RangeContainer {
+    fn attach_documentation(&mut self, documentation: String, start: Position);
+
+    /// Absorbs a contiguous run of attributes/decorators/annotations sitting
+    /// between the doc comment (if any) and the declaration - Rust
+    /// `#[derive(...)]`, Python `@decorator`, Java/TS annotations - into the
+    /// node, the same way [`Self::attach_documentation`] absorbs the doc
+    /// comment above it.
+    fn attach_decorators(&mut self, decorator_text: String, start: Position);
+}
+
+/// How many blank source lines we tolerate between two prelude items (an
+/// attribute, a doc comment, or the declaration itself) before treating them
+/// as unrelated rather than part of the same contiguous block.
+const MAX_PRELUDE_GAP_LINES: usize = 1;
+
+/// Whether `candidate_end_line` sits directly above `anchor_start_line`,
+/// allowing for up to [`MAX_PRELUDE_GAP_LINES`] blank lines in between.
+fn is_contiguous_above(candidate_end_line: usize, anchor_start_line: usize) -> bool {
+    anchor_start_line > candidate_end_line
+        && anchor_start_line - candidate_end_line <= MAX_PRELUDE_GAP_LINES + 1
+}
+
+/// Sorts `items` by start byte (widest-first on ties) and drops any item
+/// fully contained inside the one before it, keeping only the outermost,
+/// non-overlapping set. This is the folding rule shared by functions,
+/// classes and types.
+pub fn fold_ranges<T: RangeContainer + Clone>(mut items: Vec<T>) -> Vec<T> {
+    items.sort_by(|a, b| {
+        a.symbol_range()
+            .start_byte()
+            .cmp(&b.symbol_range().start_byte())
+            .then_with(|| b.symbol_range().end_byte().cmp(&a.symbol_range().end_byte()))
+    });
+
+    let mut filtered = Vec::new();
+    let mut index = 0;
+    while index < items.len() {
+        filtered.push(items[index].clone());
+        let mut iterate_index = index + 1;
+        while iterate_index < items.len()
+            && items[index]
+                .symbol_range()
+                .is_contained(&items[iterate_index].symbol_range())
+        {
+            iterate_index += 1;
         }
+        index = iterate_index;
     }
+    filtered
+}
+
+/// Sorts `blocks` and merges the concatenated `documentation_entries` into
+/// whichever block sits directly below each doc comment, the shared
+/// implementation behind `add_documentation_to_functions/_classes/_types`.
+///
+/// `attribute_entries` are the caller-supplied "prelude" ranges - attributes,
+/// decorators, annotations - that can sit between a doc comment and the
+/// declaration it documents. `language` selects the [`LanguageCommentConfig`]
+/// used to classify and beautify the raw comment text, so the same merge
+/// logic works across Rust, Python, JS/TS, Go and Shell instead of assuming
+/// `//`/`/* */`. We walk upward from each block, contiguously
+/// (tolerating blank-line gaps) absorbing first the attributes directly above
+/// it and then the doc comment above those, so the block's final range spans
+/// the whole decorated-and-documented region instead of stopping the moment
+/// an attribute breaks the old exact `end_line == start_line - 1` check.
+pub fn attach_documentation_entries<T: DocumentedRangeContainer + Clone>(
+    mut blocks: Vec<T>,
+    documentation_entries: Vec<(Range, String)>,
+    attribute_entries: Vec<(Range, String)>,
+    language: &str,
+) -> Vec<T> {
+    let comment_config = comment_config_for_language(language);
+    blocks.sort_by(|a, b| {
+        a.symbol_range()
+            .start_byte()
+            .cmp(&b.symbol_range().start_byte())
+            .then_with(|| b.symbol_range().end_byte().cmp(&a.symbol_range().end_byte()))
+    });
+    // classify every raw comment up front - we still keep non-doc entries in
+    // the list (rather than discarding them) so a plain `//`/`/* */` comment
+    // sitting directly above a declaration still occupies that line for the
+    // contiguity check below; dropping it outright would let the search
+    // bridge straight over it and misattach whatever doc comment sits one
+    // line further up to the wrong declaration
+    let documentation_entries = documentation_entries
+        .into_iter()
+        .map(|(range, text)| {
+            let kind = classify_comment(&text, &comment_config);
+            (range, text, kind)
+        })
+        .collect();
+    let documentation_entries = concat_documentation_string(documentation_entries, &comment_config);
+    let mut attribute_entries = attribute_entries;
+    attribute_entries.sort_by(|a, b| a.0.start_byte().cmp(&b.0.start_byte()));
+
+    // (end_byte, end_line, start_line) of every block processed so far that
+    // could still *contain* the one coming up (an outer function/class a
+    // nested one sits inside). Anything whose end_byte falls before this
+    // block starts has genuinely finished - we pop those off and track the
+    // highest end_line among them as the floor a doc/attribute lookup must
+    // not cross, so a doc/attribute belonging to an earlier sibling never
+    // also gets pulled into this block just because the blank-line
+    // tolerance alone would allow the jump. If a container is left on the
+    // stack, this block is nested inside it, and the container's own
+    // start_line becomes the floor instead - a nested block can still reach
+    // its own doc/attribute directly above it, but never one sitting above
+    // (or on) the enclosing declaration itself.
+    let mut open_containers: Vec<(usize, usize, usize)> = Vec::new();
+
+    blocks
+        .into_iter()
+        .map(|mut block| {
+            let mut current_floor: Option<usize> = None;
+            while open_containers
+                .last()
+                .map_or(false, |(end_byte, _, _)| *end_byte <= block.symbol_range().start_byte())
+            {
+                let (_, end_line, _) = open_containers.pop().unwrap();
+                current_floor = Some(current_floor.map_or(end_line, |floor| floor.max(end_line)));
+            }
+            if let Some((_, _, enclosing_start_line)) = open_containers.last() {
+                current_floor =
+                    Some(current_floor.map_or(*enclosing_start_line, |floor| floor.max(*enclosing_start_line)));
+            }
+            open_containers.push((
+                block.symbol_range().end_byte(),
+                block.symbol_range().end_line(),
+                block.symbol_range().start_line(),
+            ));
+
+            let above_floor = |line: usize| current_floor.map_or(true, |floor| line > floor);
+
+            // the line we're currently looking just above of; starts at the
+            // declaration itself and climbs as we absorb prelude items
+            let mut anchor_line = block.symbol_range().start_line();
+
+            // absorb every attribute/decorator directly above the
+            // declaration, walking upward one at a time so a stack of
+            // several decorators is absorbed in full
+            let mut decorator_fragments = Vec::new();
+            let mut decorator_start = None;
+            while let Some((range, text)) = attribute_entries.iter().rev().find(|(range, _)| {
+                is_contiguous_above(range.end_line(), anchor_line) && above_floor(range.start_line())
+            }) {
+                decorator_fragments.push(text.to_owned());
+                anchor_line = range.start_line();
+                decorator_start = Some(range.start_position());
+            }
+            if let Some(decorator_start) = decorator_start {
+                decorator_fragments.reverse();
+                block.attach_decorators(decorator_fragments.join("\n"), decorator_start);
+            }
+
+            // find the nearest entry directly above the (possibly
+            // attribute-expanded) anchor, doc comment or not - a non-doc
+            // comment occupying that spot blocks the search just like an
+            // unrelated sibling declaration would, it just has nothing to
+            // attach
+            if let Some(documentation_entry) = documentation_entries
+                .iter()
+                .rev()
+                .find(|(range, _, _)| {
+                    is_contiguous_above(range.end_line(), anchor_line) && above_floor(range.start_line())
+                })
+                .filter(|(_, _, kind)| kind.doc == DocDirection::Outer)
+            {
+                block.attach_documentation(
+                    documentation_entry.1.to_owned(),
+                    documentation_entry.0.start_position(),
+                );
+            }
+
+            block
+        })
+        .collect()
 }
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
 pub enum FunctionNodeType {
     // The identifier for the function
     Identifier,
@@ -198,7 +702,7 @@ impl FunctionNodeType {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub struct FunctionInformation {
     range: Range,
     r#type: FunctionNodeType,
@@ -232,6 +736,12 @@ impl FunctionInformation {
         }
     }
 
+    pub fn set_decorators(&mut self, decorators: String) {
+        if let Some(node_information) = &mut self.node_information {
+            node_information.set_decorators(decorators);
+        }
+    }
+
     pub fn insert_identifier_node(&mut self, identiifer_name: String, identifier_range: Range) {
         if let Some(node_information) = &mut self.node_information {
             node_information.set_variable_name(identiifer_name, identifier_range);
@@ -254,23 +764,15 @@ impl FunctionInformation {
         file_content[self.range().start_byte()..self.range().end_byte()].to_owned()
     }
 
+    /// `function_blocks` must already be sorted by `start_byte` ascending
+    /// (the order every `fold_*` routine in this module produces).
     pub fn find_function_in_byte_offset<'a>(
         function_blocks: &'a [&'a Self],
         byte_offset: usize,
     ) -> Option<&'a Self> {
-        let mut possible_function_block = None;
-        for function_block in function_blocks.into_iter() {
-            // if the end byte for this block is greater than the current byte
-            // position and the start byte is greater than the current bytes
-            // position as well, we have our function block
-            if !(function_block.range().end_byte() < byte_offset) {
-                if function_block.range().start_byte() > byte_offset {
-                    break;
-                }
-                possible_function_block = Some(function_block);
-            }
-        }
-        possible_function_block.copied()
+        RangeIndex::build(function_blocks)
+            .enclosing_index(byte_offset)
+            .map(|index| function_blocks[index])
     }
 
     pub fn get_expanded_selection_range(
@@ -308,75 +810,17 @@ impl FunctionInformation {
         Range::new(start_position, end_position)
     }
 
-    pub fn fold_function_blocks(mut function_blocks: Vec<Self>) -> Vec<Self> {
-        // First we sort the function blocks(which are bodies) based on the start
-        // index or the end index
-        function_blocks.sort_by(|a, b| {
-            a.range()
-                .start_byte()
-                .cmp(&b.range().start_byte())
-                .then_with(|| b.range().end_byte().cmp(&a.range().end_byte()))
-        });
-
-        // Now that these are sorted we only keep the ones which are not overlapping
-        // or fully contained in the other one
-        let mut filtered_function_blocks = Vec::new();
-        let mut index = 0;
-
-        while index < function_blocks.len() {
-            filtered_function_blocks.push(function_blocks[index].clone());
-            let mut iterate_index = index + 1;
-            while iterate_index < function_blocks.len()
-                && function_blocks[index]
-                    .range()
-                    .is_contained(&function_blocks[iterate_index].range())
-            {
-                iterate_index += 1;
-            }
-            index = iterate_index;
-        }
-
-        filtered_function_blocks
+    pub fn fold_function_blocks(function_blocks: Vec<Self>) -> Vec<Self> {
+        fold_ranges(function_blocks)
     }
 
     pub fn add_documentation_to_functions(
-        mut function_blocks: Vec<Self>,
+        function_blocks: Vec<Self>,
         documentation_entries: Vec<(Range, String)>,
+        attribute_entries: Vec<(Range, String)>,
+        language: &str,
     ) -> Vec<Self> {
-        // First we sort the function blocks based on the start index or the end index
-        function_blocks.sort_by(|a, b| {
-            a.range()
-                .start_byte()
-                .cmp(&b.range().start_byte())
-                .then_with(|| b.range().end_byte().cmp(&a.range().end_byte()))
-        });
-        let documentation_entires = concat_documentation_string(documentation_entries);
-        // now we want to concat the functions to the documentation strings
-        // we will use a 2 pointer approach here and keep track of what the current function is and what the current documentation string is
-        function_blocks
-            .into_iter()
-            .map(|mut function_block| {
-                documentation_entires
-                    .iter()
-                    .for_each(|documentation_entry| {
-                        if function_block.range().start_line() != 0
-                            && documentation_entry.0.end_line()
-                                == function_block.range().start_line() - 1
-                        {
-                            // we have a documentation entry which is right above the function block
-                            // we will add this to the function block
-                            function_block.set_documentation(documentation_entry.1.to_owned());
-                            // we will also update the function block range to include the documentation entry
-                            function_block
-                                .range
-                                .set_start_position(documentation_entry.0.start_position());
-                        }
-                    });
-                // Here we will look for the documentation entries which are just one line above the function range and add that to the function
-                // context and update the function block range
-                function_block
-            })
-            .collect()
+        attach_documentation_entries(function_blocks, documentation_entries, attribute_entries, language)
     }
 
     pub fn add_identifier_nodes(
@@ -390,23 +834,24 @@ impl FunctionInformation {
                 .cmp(&b.range().start_byte())
                 .then_with(|| b.range().end_byte().cmp(&a.range().end_byte()))
         });
-        function_blocks
+        // function_blocks are sorted and, by the time this is called, already
+        // folded down to the non-overlapping top-level blocks, so a given
+        // identifier can only ever land inside a single enclosing block.
+        let index = RangeIndex::build(&function_blocks);
+        identifier_nodes
             .into_iter()
-            .map(|mut function_block| {
-                identifier_nodes.iter().for_each(|identifier_node| {
-                    let name = &identifier_node.0;
-                    let range = identifier_node.1;
-                    if function_block.range().contains(&range) {
-                        function_block.insert_identifier_node(name.to_owned(), range);
+            .for_each(|(name, range)| {
+                if let Some(position) = index.enclosing_index(range.start_byte()) {
+                    if function_blocks[position].range().contains(&range) {
+                        function_blocks[position].insert_identifier_node(name, range);
                     }
-                });
-                function_block
-            })
-            .collect()
+                }
+            });
+        function_blocks
     }
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
 pub enum ClassNodeType {
     Identifier,
     ClassDeclaration,
@@ -422,12 +867,46 @@ impl ClassNodeType {
     }
 }
 
-#[derive(Debug, Clone)]
+impl RangeContainer for FunctionInformation {
+    fn symbol_range(&self) -> &Range {
+        &self.range
+    }
+}
+
+impl DocumentedRangeContainer for FunctionInformation {
+    fn attach_documentation(&mut self, documentation: String, start: Position) {
+        self.set_documentation(documentation);
+        self.range.set_start_position(start);
+    }
+
+    fn attach_decorators(&mut self, decorator_text: String, start: Position) {
+        self.set_decorators(decorator_text);
+        self.range.set_start_position(start);
+    }
+}
+
+impl HasDocumentationLinks for FunctionInformation {
+    fn documentation_text(&self) -> Option<&str> {
+        self.node_information
+            .as_ref()
+            .and_then(|node_information| node_information.get_documentation())
+    }
+
+    fn set_documentation_links(&mut self, links: Vec<DocumentationLink>) {
+        if let Some(node_information) = &mut self.node_information {
+            node_information.set_documentation_links(links);
+        }
+    }
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub struct ClassInformation {
     range: Range,
     name: String,
     class_node_type: ClassNodeType,
     documentation: Option<String>,
+    documentation_links: Vec<DocumentationLink>,
+    decorators: Option<String>,
 }
 
 impl ClassInformation {
@@ -437,6 +916,8 @@ impl ClassInformation {
             name,
             class_node_type,
             documentation: None,
+            documentation_links: Vec::new(),
+            decorators: None,
         }
     }
 
@@ -460,83 +941,69 @@ impl ClassInformation {
         self.documentation = Some(documentation);
     }
 
-    pub fn content(&self, content: &str) -> String {
-        content[self.range().start_byte()..self.range().end_byte()].to_string()
+    pub fn set_decorators(&mut self, decorators: String) {
+        self.decorators = Some(decorators);
     }
 
-    pub fn fold_class_information(mut classes: Vec<Self>) -> Vec<Self> {
-        // First we sort the function blocks(which are bodies) based on the start
-        // index or the end index
-        classes.sort_by(|a, b| {
-            a.range()
-                .start_byte()
-                .cmp(&b.range().start_byte())
-                .then_with(|| b.range().end_byte().cmp(&a.range().end_byte()))
-        });
+    pub fn get_decorators(&self) -> Option<&str> {
+        self.decorators.as_deref()
+    }
 
-        // Now that these are sorted we only keep the ones which are not overlapping
-        // or fully contained in the other one
-        let mut filtered_classes = Vec::new();
-        let mut index = 0;
-
-        while index < classes.len() {
-            filtered_classes.push(classes[index].clone());
-            let mut iterate_index = index + 1;
-            while iterate_index < classes.len()
-                && classes[index]
-                    .range()
-                    .is_contained(&classes[iterate_index].range())
-            {
-                iterate_index += 1;
-            }
-            index = iterate_index;
-        }
+    pub fn get_documentation(&self) -> Option<&str> {
+        self.documentation.as_deref()
+    }
+
+    pub fn get_documentation_links(&self) -> &[DocumentationLink] {
+        &self.documentation_links
+    }
+
+    pub fn content(&self, content: &str) -> String {
+        content[self.range().start_byte()..self.range().end_byte()].to_string()
+    }
 
-        filtered_classes
+    pub fn fold_class_information(classes: Vec<Self>) -> Vec<Self> {
+        fold_ranges(classes)
     }
 
     pub fn add_documentation_to_classes(
-        mut class_blocks: Vec<Self>,
+        class_blocks: Vec<Self>,
         documentation_entries: Vec<(Range, String)>,
+        attribute_entries: Vec<(Range, String)>,
+        language: &str,
     ) -> Vec<Self> {
-        // First we sort the function blocks based on the start index or the end index
-        class_blocks.sort_by(|a, b| {
-            a.range()
-                .start_byte()
-                .cmp(&b.range().start_byte())
-                .then_with(|| b.range().end_byte().cmp(&a.range().end_byte()))
-        });
-        let documentation_entires = concat_documentation_string(documentation_entries);
-        // now we want to concat the functions to the documentation strings
-        // we will use a 2 pointer approach here and keep track of what the current function is and what the current documentation string is
-        class_blocks
-            .into_iter()
-            .map(|mut class_block| {
-                documentation_entires
-                    .iter()
-                    .for_each(|documentation_entry| {
-                        if class_block.range().start_line() != 0
-                            && documentation_entry.0.end_line()
-                                == class_block.range().start_line() - 1
-                        {
-                            // we have a documentation entry which is right above the function block
-                            // we will add this to the function block
-                            class_block.set_documentation(documentation_entry.1.to_owned());
-                            // we will also update the function block range to include the documentation entry
-                            class_block
-                                .range
-                                .set_start_position(documentation_entry.0.start_position());
-                        }
-                    });
-                // Here we will look for the documentation entries which are just one line above the function range and add that to the function
-                // context and update the function block range
-                class_block
-            })
-            .collect()
+        attach_documentation_entries(class_blocks, documentation_entries, attribute_entries, language)
+    }
+}
+
+impl RangeContainer for ClassInformation {
+    fn symbol_range(&self) -> &Range {
+        &self.range
+    }
+}
+
+impl DocumentedRangeContainer for ClassInformation {
+    fn attach_documentation(&mut self, documentation: String, start: Position) {
+        self.set_documentation(documentation);
+        self.range.set_start_position(start);
+    }
+
+    fn attach_decorators(&mut self, decorator_text: String, start: Position) {
+        self.set_decorators(decorator_text);
+        self.range.set_start_position(start);
+    }
+}
+
+impl HasDocumentationLinks for ClassInformation {
+    fn documentation_text(&self) -> Option<&str> {
+        self.documentation.as_deref()
+    }
+
+    fn set_documentation_links(&mut self, links: Vec<DocumentationLink>) {
+        self.documentation_links = links;
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub struct ClassWithFunctions {
     pub class_information: Option<ClassInformation>,
     pub function_information: Vec<FunctionInformation>,
@@ -561,18 +1028,20 @@ impl ClassWithFunctions {
     }
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
 pub enum TypeNodeType {
     Identifier,
     TypeDeclaration,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub struct TypeInformation {
     pub range: Range,
     pub name: String,
     pub node_type: TypeNodeType,
     pub documentation: Option<String>,
+    pub documentation_links: Vec<DocumentationLink>,
+    pub decorators: Option<String>,
 }
 
 impl TypeNodeType {
@@ -592,6 +1061,8 @@ impl TypeInformation {
             name,
             node_type: type_node_type,
             documentation: None,
+            documentation_links: Vec::new(),
+            decorators: None,
         }
     }
 
@@ -607,6 +1078,14 @@ impl TypeInformation {
         self.documentation = Some(documentation);
     }
 
+    pub fn set_decorators(&mut self, decorators: String) {
+        self.decorators = Some(decorators);
+    }
+
+    pub fn get_decorators(&self) -> Option<&str> {
+        self.decorators.as_deref()
+    }
+
     pub fn get_type_type(&self) -> &TypeNodeType {
         &self.node_type
     }
@@ -619,146 +1098,1572 @@ impl TypeInformation {
         content[self.range().start_byte()..self.range().end_byte()].to_string()
     }
 
-    pub fn fold_type_information(mut types: Vec<Self>) -> Vec<Self> {
-        // First we sort the function blocks(which are bodies) based on the start
-        // index or the end index
-        types.sort_by(|a, b| {
-            a.range()
-                .start_byte()
-                .cmp(&b.range().start_byte())
-                .then_with(|| b.range().end_byte().cmp(&a.range().end_byte()))
-        });
-
-        // Now that these are sorted we only keep the ones which are not overlapping
-        // or fully contained in the other one
-        let mut filtered_types = Vec::new();
-        let mut index = 0;
-
-        while index < types.len() {
-            filtered_types.push(types[index].clone());
-            let mut iterate_index = index + 1;
-            while iterate_index < types.len()
-                && types[index]
-                    .range()
-                    .is_contained(&types[iterate_index].range())
-            {
-                iterate_index += 1;
-            }
-            index = iterate_index;
-        }
-
-        filtered_types
+    pub fn fold_type_information(types: Vec<Self>) -> Vec<Self> {
+        fold_ranges(types)
     }
 
     pub fn add_documentation_to_types(
-        mut type_blocks: Vec<Self>,
+        type_blocks: Vec<Self>,
         documentation_entries: Vec<(Range, String)>,
+        attribute_entries: Vec<(Range, String)>,
+        language: &str,
     ) -> Vec<Self> {
-        // First we sort the function blocks based on the start index or the end index
-        type_blocks.sort_by(|a, b| {
-            a.range()
-                .start_byte()
-                .cmp(&b.range().start_byte())
-                .then_with(|| b.range().end_byte().cmp(&a.range().end_byte()))
-        });
-        let documentation_entires = concat_documentation_string(documentation_entries);
-        // now we want to concat the functions to the documentation strings
-        // we will use a 2 pointer approach here and keep track of what the current function is and what the current documentation string is
-        type_blocks
-            .into_iter()
-            .map(|mut type_block| {
-                documentation_entires
-                    .iter()
-                    .for_each(|documentation_entry| {
-                        if type_block.range().start_line() != 0
-                            && documentation_entry.0.end_line()
-                                == type_block.range().start_line() - 1
-                        {
-                            // we have a documentation entry which is right above the function block
-                            // we will add this to the function block
-                            type_block.set_documentation(documentation_entry.1.to_owned());
-                            // we will also update the function block range to include the documentation entry
-                            type_block
-                                .range
-                                .set_start_position(documentation_entry.0.start_position());
-                        }
-                    });
-                // Here we will look for the documentation entries which are just one line above the function range and add that to the function
-                // context and update the function block range
-                type_block
-            })
-            .collect()
+        attach_documentation_entries(type_blocks, documentation_entries, attribute_entries, language)
     }
 }
 
-pub fn concat_documentation_string(
-    mut documentation_entries: Vec<(Range, String)>,
-) -> Vec<(Range, String)> {
-    // we also sort the doucmentation entries based on the start index or the end index
-    documentation_entries.sort_by(|a, b| {
-        a.0.start_byte()
-            .cmp(&b.0.start_byte())
-            .then_with(|| b.0.end_byte().cmp(&a.0.end_byte()))
-    });
-    // We also want to concat the documentation entires if they are right after one another for example:
-    // // This is a comment
-    // // This is another comment
-    // fn foo() {}
-    // We want to make sure that we concat the comments into one
-    let mut documentation_index = 0;
-    let mut concatenated_documentation_queries: Vec<(Range, String)> = Vec::new();
-    while documentation_index < documentation_entries.len() {
-        let mut iterate_index = documentation_index + 1;
-        let mut current_index_end_line = documentation_entries[documentation_index].0.end_line();
-        let mut documentation_str = documentation_entries[documentation_index].1.to_owned();
-        let mut documentation_range = documentation_entries[documentation_index].0.clone();
-
-        // iterate over consecutive entries in the comments
-        while iterate_index < documentation_entries.len()
-            && current_index_end_line + 1 == documentation_entries[iterate_index].0.start_line()
-        {
-            current_index_end_line = documentation_entries[iterate_index].0.end_line();
-            documentation_str = documentation_str + "\n" + &documentation_entries[iterate_index].1;
-            documentation_range
-                .set_end_position(documentation_entries[iterate_index].0.end_position());
-            iterate_index += 1;
-        }
-        concatenated_documentation_queries.push((documentation_range, documentation_str));
-        documentation_index = iterate_index;
-        // either we hit the end of we have a bunch of documentation entries which are consecutive
-        // we know what the comment should be and we can add a new entry
+impl RangeContainer for TypeInformation {
+    fn symbol_range(&self) -> &Range {
+        &self.range
+    }
+}
+
+impl DocumentedRangeContainer for TypeInformation {
+    fn attach_documentation(&mut self, documentation: String, start: Position) {
+        self.set_documentation(documentation);
+        self.range.set_start_position(start);
+    }
+
+    fn attach_decorators(&mut self, decorator_text: String, start: Position) {
+        self.set_decorators(decorator_text);
+        self.range.set_start_position(start);
+    }
+}
+
+impl HasDocumentationLinks for TypeInformation {
+    fn documentation_text(&self) -> Option<&str> {
+        self.documentation.as_deref()
+    }
+
+    fn set_documentation_links(&mut self, links: Vec<DocumentationLink>) {
+        self.documentation_links = links;
+    }
+}
+
+/// A recursive symbol tree node: unlike `ClassInformation`/`TypeInformation`/
+/// `FunctionInformation`, which are flat lists that cannot express nesting,
+/// a `SymbolNode` can hold children of arbitrary depth. [`symbol_tree`] is
+/// what actually builds one of these out of those flat lists - a class
+/// becomes a node with its methods as children (the one level of real
+/// nesting `ClassWithFunctions` already pairs up), everything else is a
+/// childless leaf.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct SymbolNode {
+    range: Range,
+    name: String,
+    kind: OutlineNodeType,
+    documentation: Option<String>,
+    documentation_links: Vec<DocumentationLink>,
+    /// The attribute/decorator/annotation text absorbed directly above this
+    /// node's declaration (and below its doc comment, if any).
+    decorators: Option<String>,
+    children: Vec<SymbolNode>,
+}
+
+impl SymbolNode {
+    pub fn new(range: Range, name: String, kind: OutlineNodeType) -> Self {
+        Self {
+            range,
+            name,
+            kind,
+            documentation: None,
+            documentation_links: Vec::new(),
+            decorators: None,
+            children: Vec::new(),
+        }
+    }
+
+    pub fn with_children(mut self, children: Vec<SymbolNode>) -> Self {
+        self.children = children;
+        self
+    }
+
+    pub fn range(&self) -> &Range {
+        &self.range
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn kind(&self) -> &OutlineNodeType {
+        &self.kind
+    }
+
+    pub fn children(&self) -> &[SymbolNode] {
+        &self.children
+    }
+
+    pub fn push_child(&mut self, child: SymbolNode) {
+        self.children.push(child);
+    }
+
+    pub fn documentation(&self) -> Option<&str> {
+        self.documentation.as_deref()
+    }
+
+    pub fn documentation_links(&self) -> &[DocumentationLink] {
+        &self.documentation_links
+    }
+
+    pub fn decorators(&self) -> Option<&str> {
+        self.decorators.as_deref()
+    }
+
+    /// One leaf node per fully-captured function in `functions` - entries
+    /// whose `FunctionNodeType` isn't `Function` (a lone `identifier`,
+    /// `parameters` or `body` capture) or that never got a name attached
+    /// aren't a complete symbol on their own, so they're skipped rather than
+    /// surfaced as a bare, nameless node.
+    fn leaves_from_functions(functions: &[FunctionInformation]) -> Vec<Self> {
+        functions
+            .iter()
+            .filter(|function| *function.r#type() == FunctionNodeType::Function)
+            .filter_map(Self::leaf_from_function)
+            .collect()
+    }
+
+    fn leaf_from_function(function: &FunctionInformation) -> Option<Self> {
+        let name = function.name()?.to_owned();
+        let node_information = function.get_node_information();
+        let mut node = Self::new(function.range().clone(), name, OutlineNodeType::Function);
+        node.documentation = node_information.and_then(|info| info.get_documentation()).map(str::to_owned);
+        node.documentation_links = node_information
+            .map(|info| info.get_documentation_links().to_owned())
+            .unwrap_or_default();
+        node.decorators = node_information.and_then(|info| info.get_decorators()).map(str::to_owned);
+        Some(node)
+    }
+
+    fn leaf_from_type(type_information: &TypeInformation) -> Self {
+        let mut node = Self::new(
+            type_information.range().clone(),
+            type_information.get_name().to_owned(),
+            OutlineNodeType::TypeAlias,
+        );
+        node.documentation = type_information.documentation.clone();
+        node.documentation_links = type_information.documentation_links.clone();
+        node.decorators = type_information.decorators.clone();
+        node
+    }
+
+    /// A class node with every one of its methods as a direct child - `None`
+    /// when `group` has no `class_information` (a bare function group with
+    /// nothing to root the node at).
+    fn from_class_with_functions(group: &ClassWithFunctions) -> Option<Self> {
+        let class = group.class_information.as_ref()?;
+        let mut node = Self::new(class.range().clone(), class.get_name().to_owned(), OutlineNodeType::Class);
+        node.documentation = class.get_documentation().map(str::to_owned);
+        node.documentation_links = class.get_documentation_links().to_owned();
+        node.decorators = class.get_decorators().map(str::to_owned);
+        node.children = Self::leaves_from_functions(&group.function_information);
+        Some(node)
+    }
+}
+
+impl RangeContainer for SymbolNode {
+    fn symbol_range(&self) -> &Range {
+        &self.range
+    }
+}
+
+impl DocumentedRangeContainer for SymbolNode {
+    fn attach_documentation(&mut self, documentation: String, start: Position) {
+        self.documentation = Some(documentation);
+        self.range.set_start_position(start);
+    }
+
+    fn attach_decorators(&mut self, decorator_text: String, start: Position) {
+        self.decorators = Some(decorator_text);
+        self.range.set_start_position(start);
+    }
+}
+
+impl HasDocumentationLinks for SymbolNode {
+    fn documentation_text(&self) -> Option<&str> {
+        self.documentation.as_deref()
+    }
+
+    fn set_documentation_links(&mut self, links: Vec<DocumentationLink>) {
+        self.documentation_links = links;
+    }
+}
+
+/// Assembles a file's `ClassWithFunctions` groups, any functions outside a
+/// class, and its types into one recursive symbol forest: each class becomes
+/// a node with its methods as children, everything else is a childless leaf
+/// alongside it. This is the actual traversal structure downstream consumers
+/// should walk instead of re-deriving class/method nesting from the flat
+/// `ClassInformation`/`FunctionInformation`/`TypeInformation` lists by hand.
+pub fn symbol_tree(
+    classes_with_functions: &[ClassWithFunctions],
+    standalone_functions: &[FunctionInformation],
+    types: &[TypeInformation],
+) -> Vec<SymbolNode> {
+    let mut nodes: Vec<SymbolNode> = classes_with_functions
+        .iter()
+        .filter_map(SymbolNode::from_class_with_functions)
+        .collect();
+    nodes.extend(SymbolNode::leaves_from_functions(standalone_functions));
+    nodes.extend(types.iter().map(SymbolNode::leaf_from_type));
+    nodes
+}
+
+/// Which comment delimiter a raw comment uses. `Docstring` covers languages
+/// like Python where documentation is a string literal (`"""`/`'''`) sitting
+/// inside the declaration's body rather than a comment token at all.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum CommentShape {
+    Line,
+    Block,
+    Docstring,
+}
+
+/// Whether a comment documents the item that follows it (`///`, `/** */`),
+/// the scope it's written inside of (`//!`, `/*! */`, or a Python docstring -
+/// which sits *inside* the body it documents rather than above it), or isn't
+/// a doc comment at all (a plain `//`/`/* */`, or the `////`/`/***` variants
+/// rustc also treats as plain comments).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum DocDirection {
+    Outer,
+    Inner,
+    None,
+}
+
+/// The classification of a single raw comment: its delimiter shape plus its
+/// doc-direction. Two comments only belong to the same merged doc block if
+/// their `CommentKind`s match - a plain `//` comment sitting directly above
+/// a `///` doc comment must not be folded into it, and an inner `//!` run
+/// must stay separate from an adjacent outer `///` run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct CommentKind {
+    pub shape: CommentShape,
+    pub doc: DocDirection,
+}
+
+/// One doc-comment marker and the [`DocDirection`] it signals, e.g.
+/// `("///", DocDirection::Outer)`. [`LanguageCommentConfig`] lists these
+/// longest-prefix-first so a four-character marker like `////` is tried
+/// before the three-character `///` it would otherwise be mistaken for.
+pub type CommentMarker = (&'static str, DocDirection);
+
+/// A language's block-comment grammar: the open markers that double as doc
+/// indicators (longest-first, same convention as [`CommentMarker`]) plus the
+/// close token every one of them shares.
+#[derive(Debug, Clone, Copy)]
+pub struct BlockCommentTokens {
+    pub open_markers: &'static [CommentMarker],
+    pub close: &'static str,
+}
+
+/// Describes one language's documentation grammar, so [`classify_comment`]
+/// and [`beautify_doc_string`] aren't locked to Rust's `///`/`//!`/`/** */`
+/// conventions. A comment's *shape* (line, block, or docstring) is detected
+/// structurally from the raw text; this config only supplies, per shape,
+/// which markers count as documentation and in which direction they point.
+/// Build one with [`comment_config_for_language`].
+#[derive(Debug, Clone, Copy)]
+pub struct LanguageCommentConfig {
+    pub line_markers: &'static [CommentMarker],
+    pub block_comments: BlockCommentTokens,
+    /// String-literal docstring delimiters (Python's `"""`/`'''`). Empty for
+    /// languages that don't document via a string literal.
+    pub docstring_delimiters: &'static [&'static str],
+}
+
+/// Builds the comment grammar for one of the languages the chunker
+/// supports. Docstring languages (Python) attach as [`DocDirection::Inner`]:
+/// a docstring sits *inside* the body of the declaration it documents (right
+/// after the `def`/`class` line), the same "documents its enclosing scope"
+/// relationship as a Rust `//!`, so it binds to the smallest declaration
+/// whose range contains it rather than to the next declaration below it.
+/// Unrecognised languages fall back to the plain C-family `//`/`/* */` shape
+/// with no doc/non-doc distinction - the behaviour this crate had before
+/// per-language grammars existed.
+pub fn comment_config_for_language(language: &str) -> LanguageCommentConfig {
+    match language {
+        "rust" => LanguageCommentConfig {
+            line_markers: &[
+                ("////", DocDirection::None),
+                ("///", DocDirection::Outer),
+                ("//!", DocDirection::Inner),
+                ("//", DocDirection::None),
+            ],
+            block_comments: BlockCommentTokens {
+                open_markers: &[
+                    ("/***", DocDirection::None),
+                    ("/**/", DocDirection::None),
+                    ("/**", DocDirection::Outer),
+                    ("/*!", DocDirection::Inner),
+                ],
+                close: "*/",
+            },
+            docstring_delimiters: &[],
+        },
+        "python" => LanguageCommentConfig {
+            line_markers: &[("#", DocDirection::None)],
+            block_comments: BlockCommentTokens {
+                open_markers: &[],
+                close: "*/",
+            },
+            // Python checks its own triple-quote string literals before
+            // falling back to `#`, so docstrings never get mistaken for
+            // plain comments.
+            docstring_delimiters: &["\"\"\"", "'''"],
+        },
+        "javascript" | "typescript" | "javascriptreact" | "typescriptreact" | "java" | "c"
+        | "cpp" => LanguageCommentConfig {
+            // JSDoc/Javadoc mark documentation with `/**`, not a line-comment
+            // variant - `//` stays a plain, non-doc comment in this family.
+            line_markers: &[("//", DocDirection::None)],
+            block_comments: BlockCommentTokens {
+                open_markers: &[("/**", DocDirection::Outer)],
+                close: "*/",
+            },
+            docstring_delimiters: &[],
+        },
+        "golang" | "go" => LanguageCommentConfig {
+            // godoc treats any `//` run directly above a declaration as its
+            // doc comment - there is no separate plain-vs-doc marker.
+            line_markers: &[("//", DocDirection::Outer)],
+            block_comments: BlockCommentTokens {
+                open_markers: &[],
+                close: "*/",
+            },
+            docstring_delimiters: &[],
+        },
+        "shell" | "bash" | "sh" => LanguageCommentConfig {
+            // same rationale as Go: shell has no plain-vs-doc comment
+            // distinction, so every `#` run above a declaration documents it.
+            line_markers: &[("#", DocDirection::Outer)],
+            block_comments: BlockCommentTokens {
+                open_markers: &[],
+                close: "*/",
+            },
+            docstring_delimiters: &[],
+        },
+        _ => LanguageCommentConfig {
+            line_markers: &[("//", DocDirection::Outer)],
+            block_comments: BlockCommentTokens {
+                open_markers: &[("/*", DocDirection::Outer)],
+                close: "*/",
+            },
+            docstring_delimiters: &[],
+        },
+    }
+}
+
+/// The structural shape of a raw comment string, independent of any
+/// language's marker configuration: a docstring delimiter, then a block
+/// opener, then line comment by default.
+fn raw_comment_shape(comment: &str, config: &LanguageCommentConfig) -> CommentShape {
+    let trimmed = comment.trim_start();
+    if config
+        .docstring_delimiters
+        .iter()
+        .any(|delimiter| trimmed.starts_with(delimiter))
+    {
+        CommentShape::Docstring
+    } else if trimmed.starts_with("/*") {
+        CommentShape::Block
+    } else {
+        CommentShape::Line
+    }
+}
+
+/// Classifies a raw (un-beautified) comment string by shape and
+/// doc-direction, using `config` to tell which markers count as
+/// documentation for the language it was extracted from. Rust's own rule is
+/// rustc's: `///`/`/** */` document the following item, `//!`/`/*! */`
+/// document the enclosing scope, and `////`/`/***` (quadruple-slash /
+/// triple-star) are deliberately excluded, matching the escape hatch rustc
+/// itself gives for "this looks like a doc comment but isn't one".
+pub fn classify_comment(comment: &str, config: &LanguageCommentConfig) -> CommentKind {
+    let trimmed = comment.trim_start();
+    match raw_comment_shape(comment, config) {
+        CommentShape::Docstring => CommentKind {
+            shape: CommentShape::Docstring,
+            doc: DocDirection::Inner,
+        },
+        CommentShape::Block => {
+            let doc = config
+                .block_comments
+                .open_markers
+                .iter()
+                .find(|(marker, _)| trimmed.starts_with(marker))
+                .map(|(_, direction)| *direction)
+                .unwrap_or(DocDirection::None);
+            CommentKind {
+                shape: CommentShape::Block,
+                doc,
+            }
+        }
+        CommentShape::Line => {
+            let doc = config
+                .line_markers
+                .iter()
+                .find(|(marker, _)| trimmed.starts_with(marker))
+                .map(|(_, direction)| *direction)
+                .unwrap_or(DocDirection::None);
+            CommentKind {
+                shape: CommentShape::Line,
+                doc,
+            }
+        }
+    }
+}
+
+/// Strips the comment delimiters of a single raw comment string, adapted
+/// from rustc's `beautify_doc_string` and generalised with `config` to the
+/// shape [`classify_comment`] would assign it. Line comments lose their
+/// marker and one leading space only - a lone `///` line is merged with its
+/// neighbours by [`concat_documentation_string`] later, so any indentation
+/// left beyond that one space is relative to those neighbours and must be
+/// preserved rather than stripped. Block comments and docstrings, which
+/// carry their whole multi-line body in one entry, lose their
+/// opening/closing delimiters (and, for a block comment, the `*`-prefix
+/// convention if every line uses it) and are dedented to their minimum
+/// common leading-whitespace indentation, with trailing whitespace trimmed
+/// off every line.
+pub fn beautify_doc_string(comment: &str, config: &LanguageCommentConfig) -> String {
+    match raw_comment_shape(comment, config) {
+        CommentShape::Block => dedent(&strip_block_comment_markers(comment, &config.block_comments)),
+        CommentShape::Docstring => dedent(&strip_docstring_markers(comment, config.docstring_delimiters)),
+        CommentShape::Line => strip_line_comment_markers(comment, config.line_markers),
+    }
+}
+
+fn strip_line_comment_markers(comment: &str, markers: &[CommentMarker]) -> String {
+    let trimmed = comment.trim_start();
+    let without_marker = markers
+        .iter()
+        .find_map(|(marker, _)| trimmed.strip_prefix(marker))
+        .unwrap_or(trimmed);
+    without_marker
+        .strip_prefix(' ')
+        .unwrap_or(without_marker)
+        .to_owned()
+}
+
+fn strip_block_comment_markers(comment: &str, block: &BlockCommentTokens) -> String {
+    let trimmed = comment.trim();
+    let without_open = block
+        .open_markers
+        .iter()
+        .find_map(|(marker, _)| trimmed.strip_prefix(marker))
+        .unwrap_or_else(|| trimmed.strip_prefix("/*").unwrap_or(trimmed));
+    let without_close = without_open.strip_suffix(block.close).unwrap_or(without_open);
+    strip_block_asterisks(without_close)
+}
+
+/// Strips a docstring's own triple-quote delimiters (`"""`/`'''`), leaving
+/// its body untouched beyond that - unlike a block comment there is no
+/// `*`-prefix convention to also account for.
+fn strip_docstring_markers(comment: &str, delimiters: &[&str]) -> String {
+    let trimmed = comment.trim();
+    match delimiters.iter().find(|delimiter| trimmed.starts_with(**delimiter)) {
+        Some(delimiter) => {
+            let without_open = trimmed.strip_prefix(delimiter).unwrap_or(trimmed);
+            without_open
+                .strip_suffix(delimiter)
+                .unwrap_or(without_open)
+                .to_owned()
+        }
+        None => trimmed.to_owned(),
+    }
+}
+
+/// If this is a genuinely multi-line body and every non-blank line starts
+/// with an (optionally indented) `*`, strips that `*` and one following
+/// space from each line - the common `/** ... */` style where every interior
+/// line is prefixed to keep the comment visually aligned. A single-line body
+/// is left untouched even if it happens to start with `*`: there are no
+/// sibling lines for the marker convention to align with, so a leading `*`
+/// there is just literal content (e.g. `/** * TODO */`).
+fn strip_block_asterisks(body: &str) -> String {
+    let lines: Vec<&str> = body.lines().collect();
+    let all_starred = lines.len() > 1
+        && lines.iter().all(|line| {
+            let trimmed = line.trim_start();
+            trimmed.is_empty() || trimmed.starts_with('*')
+        });
+    if !all_starred {
+        return body.to_owned();
+    }
+    lines
+        .into_iter()
+        .map(|line| {
+            let trimmed = line.trim_start();
+            let after_star = trimmed.strip_prefix('*').unwrap_or(trimmed);
+            after_star.strip_prefix(' ').unwrap_or(after_star)
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Removes the minimum common leading-whitespace indentation shared by every
+/// non-blank line, and trims trailing whitespace off each line.
+fn dedent(text: &str) -> String {
+    let lines: Vec<&str> = text.lines().collect();
+    let min_indent = lines
+        .iter()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| line.chars().take_while(|c| c.is_whitespace()).count())
+        .min()
+        .unwrap_or(0);
+    lines
+        .into_iter()
+        .map(|line| line.chars().skip(min_indent).collect::<String>().trim_end().to_owned())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+pub fn concat_documentation_string(
+    mut documentation_entries: Vec<(Range, String, CommentKind)>,
+    config: &LanguageCommentConfig,
+) -> Vec<(Range, String, CommentKind)> {
+    // normalize each entry's comment markers/indentation before merging
+    // consecutive entries together, so the concatenated text is clean prose
+    // rather than raw source with `///`/`/* */` delimiters still attached
+    for entry in &mut documentation_entries {
+        entry.1 = beautify_doc_string(&entry.1, config);
+    }
+    // we also sort the doucmentation entries based on the start index or the end index
+    documentation_entries.sort_by(|a, b| {
+        a.0.start_byte()
+            .cmp(&b.0.start_byte())
+            .then_with(|| b.0.end_byte().cmp(&a.0.end_byte()))
+    });
+    // We also want to concat the documentation entires if they are right after one another for example:
+    // // This is a comment
+    // // This is another comment
+    // fn foo() {}
+    // We want to make sure that we concat the comments into one - but only
+    // when they share the same `CommentKind`, so a plain `//` comment
+    // directly above a `///` run (or an inner `//!` run directly above an
+    // outer `///` run) never gets folded into a doc block it doesn't belong to.
+    // The one exception is a blank/whitespace-only entry (e.g. a lone `//`
+    // used as a paragraph separator inside a `///` run): its `CommentKind`
+    // won't match, but it carries no content of its own to misattach, so we
+    // let it ride along as a blank line rather than splitting the run and
+    // having it fall out as a dropped, kind-`None` block of its own.
+    //
+    // Adjacency itself stays strict - `current_index_end_line + 1` requires
+    // the next entry to start on the very next source line, so even a single
+    // raw blank *source* line (not a blank comment) between two runs ends the
+    // run, keeping two doc paragraphs separated by a line of code from ever
+    // being fused together.
+    let mut documentation_index = 0;
+    let mut concatenated_documentation_queries: Vec<(Range, String, CommentKind)> = Vec::new();
+    while documentation_index < documentation_entries.len() {
+        let mut iterate_index = documentation_index + 1;
+        let mut current_index_end_line = documentation_entries[documentation_index].0.end_line();
+        let mut documentation_str = documentation_entries[documentation_index].1.to_owned();
+        let mut documentation_range = documentation_entries[documentation_index].0.clone();
+        let current_kind = documentation_entries[documentation_index].2;
+
+        // iterate over consecutive entries in the comments
+        while iterate_index < documentation_entries.len()
+            && current_index_end_line + 1 == documentation_entries[iterate_index].0.start_line()
+            && (documentation_entries[iterate_index].2 == current_kind
+                || documentation_entries[iterate_index].1.trim().is_empty())
+        {
+            current_index_end_line = documentation_entries[iterate_index].0.end_line();
+            documentation_str = documentation_str + "\n" + &documentation_entries[iterate_index].1;
+            documentation_range
+                .set_end_position(documentation_entries[iterate_index].0.end_position());
+            iterate_index += 1;
+        }
+        concatenated_documentation_queries.push((documentation_range, documentation_str, current_kind));
+        documentation_index = iterate_index;
+        // either we hit the end of we have a bunch of documentation entries which are consecutive
+        // we know what the comment should be and we can add a new entry
     }
     concatenated_documentation_queries
 }
 
+/// Where a concatenated doc block ended up: bound to the declaration it
+/// documents, or free-floating (a file-top license banner, a stray doc
+/// comment with nothing left to attach to, ...) when no declaration claims it.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub enum DocumentationBinding {
+    Symbol(Range),
+    FreeFloating,
+}
+
+/// Binds each concatenated doc block from [`concat_documentation_string`] to
+/// the declaration it documents: an outer block (`///`, `/** */`) binds to
+/// the nearest declaration starting at most [`MAX_PRELUDE_GAP_LINES`] lines
+/// below its last line, so a lone attribute/annotation line in between is
+/// skipped over rather than treated as "nothing found"; an
+/// inner block (`//!`, `/*! */`, or a Python docstring) binds to the
+/// declaration that encloses it instead, since it documents the scope it's
+/// written inside of - for a docstring that scope is the very declaration it
+/// sits just below the top of. Plain (non-doc) comment blocks are dropped -
+/// they were never doc comments to begin with. Anything left over (no
+/// declaration below it, or no enclosing declaration) is emitted as
+/// [`DocumentationBinding::FreeFloating`] so indexing can tell "documents
+/// this symbol" apart from "free-floating commentary".
+pub fn bind_documentation_to_declarations(
+    documentation_entries: Vec<(Range, String, CommentKind)>,
+    declaration_ranges: &[Range],
+) -> Vec<(String, DocumentationBinding)> {
+    documentation_entries
+        .into_iter()
+        .filter_map(|(range, text, kind)| match kind.doc {
+            DocDirection::Outer => {
+                let nearest_declaration_below = declaration_ranges
+                    .iter()
+                    .filter(|declaration| is_contiguous_above(range.end_line(), declaration.start_line()))
+                    .min_by_key(|declaration| declaration.start_line());
+                let binding = nearest_declaration_below
+                    .map(|declaration| DocumentationBinding::Symbol(declaration.clone()))
+                    .unwrap_or(DocumentationBinding::FreeFloating);
+                Some((text, binding))
+            }
+            DocDirection::Inner => {
+                let enclosing_declaration = declaration_ranges
+                    .iter()
+                    .filter(|declaration| {
+                        declaration.start_line() <= range.start_line()
+                            && declaration.end_line() >= range.end_line()
+                    })
+                    .min_by_key(|declaration| declaration.end_byte() - declaration.start_byte());
+                let binding = enclosing_declaration
+                    .map(|declaration| DocumentationBinding::Symbol(declaration.clone()))
+                    .unwrap_or(DocumentationBinding::FreeFloating);
+                Some((text, binding))
+            }
+            DocDirection::None => None,
+        })
+        .collect()
+}
+
+/// Serializable, content-hashed cache for outline trees, so a file whose
+/// bytes haven't changed since the last request never has to be re-parsed
+/// and re-walked into an outline again.
+pub mod outline_cache {
+    use std::collections::HashMap;
+
+    use super::{OutlineNode, OutlineNodeContent, OutlineNodeType};
+    use crate::chunking::text_document::{Position, Range};
+
+    /// Identifies a cached outline: the file path plus a hash of its current
+    /// byte content, so any edit to the file invalidates the entry on its own.
+    #[derive(Debug, Clone, PartialEq, Eq, Hash)]
+    struct OutlineCacheKey {
+        file_path: String,
+        content_hash: u64,
+    }
+
+    impl OutlineCacheKey {
+        fn new(file_path: &str, content: &[u8]) -> Self {
+            Self {
+                file_path: file_path.to_owned(),
+                content_hash: fnv1a_hash(content),
+            }
+        }
+    }
+
+    /// FNV-1a: small and dependency-free, which is all a cache-invalidation
+    /// hash needs to be.
+    fn fnv1a_hash(content: &[u8]) -> u64 {
+        let mut hash: u64 = 0xcbf29ce484222325;
+        for byte in content {
+            hash ^= *byte as u64;
+            hash = hash.wrapping_mul(0x100000001b3);
+        }
+        hash
+    }
+
+    #[derive(Debug)]
+    pub enum OutlineCacheError {
+        UnexpectedEof,
+        InvalidUtf8,
+        InvalidTag(u8),
+        RangeOutOfBounds,
+    }
+
+    /// An in-memory, content-hash keyed outline cache. Entries are stored in
+    /// the compact tagged binary encoding below rather than as `OutlineNode`
+    /// values directly; the cache has no method that writes them to disk or
+    /// loads them back, so it lives only as long as the value does.
+    #[derive(Default)]
+    pub struct OutlineCache {
+        entries: HashMap<OutlineCacheKey, Vec<u8>>,
+    }
+
+    impl OutlineCache {
+        pub fn new() -> Self {
+            Self::default()
+        }
+
+        /// Returns the cached outline for `file_path`/`content` on a hit. A
+        /// stale or corrupt entry (e.g. a range that no longer fits inside
+        /// `content`) is treated as a miss rather than panicking or returning
+        /// out-of-bounds ranges.
+        pub fn get(&self, file_path: &str, content: &[u8]) -> Option<Vec<OutlineNode>> {
+            let key = OutlineCacheKey::new(file_path, content);
+            let payload = self.entries.get(&key)?;
+            decode_outline_nodes(payload, content.len()).ok()
+        }
+
+        pub fn insert(&mut self, file_path: &str, content: &[u8], outline: &[OutlineNode]) {
+            let key = OutlineCacheKey::new(file_path, content);
+            self.entries.insert(key, encode_outline_nodes(outline));
+        }
+    }
+
+    // ---- compact tagged, length-prefixed encoding ----
+    //
+    // nodes := node_count:varint (node)*
+    // node := content language:string child_count:varint (content)*
+    // content := tag:u8 name:string range content:string
+    // string := len:varint bytes
+    // range := start_byte:varint end_byte:varint
+    //          start_line:varint start_column:varint
+    //          end_line:varint end_column:varint
+
+    fn write_varint(buf: &mut Vec<u8>, mut value: u64) {
+        loop {
+            let mut byte = (value & 0x7f) as u8;
+            value >>= 7;
+            if value != 0 {
+                byte |= 0x80;
+            }
+            buf.push(byte);
+            if value == 0 {
+                break;
+            }
+        }
+    }
+
+    fn write_string(buf: &mut Vec<u8>, value: &str) {
+        write_varint(buf, value.len() as u64);
+        buf.extend_from_slice(value.as_bytes());
+    }
+
+    fn write_range(buf: &mut Vec<u8>, range: &Range) {
+        write_varint(buf, range.start_byte() as u64);
+        write_varint(buf, range.end_byte() as u64);
+        write_varint(buf, range.start_position().line() as u64);
+        write_varint(buf, range.start_position().column() as u64);
+        write_varint(buf, range.end_position().line() as u64);
+        write_varint(buf, range.end_position().column() as u64);
+    }
+
+    fn tag_for_kind(kind: &OutlineNodeType) -> u8 {
+        match kind {
+            OutlineNodeType::Class => 0,
+            OutlineNodeType::ClassName => 1,
+            OutlineNodeType::Function => 2,
+            OutlineNodeType::FunctionName => 3,
+            OutlineNodeType::FunctionBody => 4,
+            OutlineNodeType::Enum => 5,
+            OutlineNodeType::EnumVariant => 6,
+            OutlineNodeType::Interface => 7,
+            OutlineNodeType::Trait => 8,
+            OutlineNodeType::Struct => 9,
+            OutlineNodeType::TypeAlias => 10,
+            OutlineNodeType::Field => 11,
+        }
+    }
+
+    fn kind_for_tag(tag: u8) -> Result<OutlineNodeType, OutlineCacheError> {
+        Ok(match tag {
+            0 => OutlineNodeType::Class,
+            1 => OutlineNodeType::ClassName,
+            2 => OutlineNodeType::Function,
+            3 => OutlineNodeType::FunctionName,
+            4 => OutlineNodeType::FunctionBody,
+            5 => OutlineNodeType::Enum,
+            6 => OutlineNodeType::EnumVariant,
+            7 => OutlineNodeType::Interface,
+            8 => OutlineNodeType::Trait,
+            9 => OutlineNodeType::Struct,
+            10 => OutlineNodeType::TypeAlias,
+            11 => OutlineNodeType::Field,
+            other => return Err(OutlineCacheError::InvalidTag(other)),
+        })
+    }
+
+    fn write_content(buf: &mut Vec<u8>, content: &OutlineNodeContent) {
+        buf.push(tag_for_kind(&content.r#type));
+        write_string(buf, &content.name);
+        write_range(buf, &content.range);
+        write_string(buf, &content.content);
+    }
+
+    fn write_node(buf: &mut Vec<u8>, node: &OutlineNode) {
+        write_content(buf, &node.content);
+        write_string(buf, &node.language);
+        write_varint(buf, node.children.len() as u64);
+        for child in &node.children {
+            write_content(buf, child);
+        }
+    }
+
+    fn encode_outline_nodes(nodes: &[OutlineNode]) -> Vec<u8> {
+        let mut buf = Vec::new();
+        write_varint(&mut buf, nodes.len() as u64);
+        for node in nodes {
+            write_node(&mut buf, node);
+        }
+        buf
+    }
+
+    struct Cursor<'a> {
+        data: &'a [u8],
+        position: usize,
+    }
+
+    impl<'a> Cursor<'a> {
+        fn new(data: &'a [u8]) -> Self {
+            Self { data, position: 0 }
+        }
+
+        fn read_u8(&mut self) -> Result<u8, OutlineCacheError> {
+            let byte = *self
+                .data
+                .get(self.position)
+                .ok_or(OutlineCacheError::UnexpectedEof)?;
+            self.position += 1;
+            Ok(byte)
+        }
+
+        fn read_varint(&mut self) -> Result<u64, OutlineCacheError> {
+            let mut result = 0u64;
+            let mut shift = 0;
+            loop {
+                let byte = self.read_u8()?;
+                result |= ((byte & 0x7f) as u64) << shift;
+                if byte & 0x80 == 0 {
+                    break;
+                }
+                shift += 7;
+            }
+            Ok(result)
+        }
+
+        fn read_bytes(&mut self, len: usize) -> Result<&'a [u8], OutlineCacheError> {
+            let end = self
+                .position
+                .checked_add(len)
+                .ok_or(OutlineCacheError::UnexpectedEof)?;
+            let slice = self
+                .data
+                .get(self.position..end)
+                .ok_or(OutlineCacheError::UnexpectedEof)?;
+            self.position = end;
+            Ok(slice)
+        }
+
+        fn read_string(&mut self) -> Result<String, OutlineCacheError> {
+            let len = self.read_varint()? as usize;
+            let bytes = self.read_bytes(len)?;
+            String::from_utf8(bytes.to_vec()).map_err(|_| OutlineCacheError::InvalidUtf8)
+        }
+    }
+
+    fn read_range(cursor: &mut Cursor, content_len: usize) -> Result<Range, OutlineCacheError> {
+        let start_byte = cursor.read_varint()? as usize;
+        let end_byte = cursor.read_varint()? as usize;
+        let start_line = cursor.read_varint()? as usize;
+        let start_column = cursor.read_varint()? as usize;
+        let end_line = cursor.read_varint()? as usize;
+        let end_column = cursor.read_varint()? as usize;
+        if start_byte > content_len || end_byte > content_len || start_byte > end_byte {
+            return Err(OutlineCacheError::RangeOutOfBounds);
+        }
+        Ok(Range::new(
+            Position::new(start_line, start_column, start_byte),
+            Position::new(end_line, end_column, end_byte),
+        ))
+    }
+
+    fn read_content(
+        cursor: &mut Cursor,
+        content_len: usize,
+    ) -> Result<OutlineNodeContent, OutlineCacheError> {
+        let tag = cursor.read_u8()?;
+        let r#type = kind_for_tag(tag)?;
+        let name = cursor.read_string()?;
+        let range = read_range(cursor, content_len)?;
+        let content = cursor.read_string()?;
+        Ok(OutlineNodeContent::new(name, range, r#type, content))
+    }
+
+    fn read_node(cursor: &mut Cursor, content_len: usize) -> Result<OutlineNode, OutlineCacheError> {
+        let content = read_content(cursor, content_len)?;
+        let language = cursor.read_string()?;
+        let child_count = cursor.read_varint()? as usize;
+        let mut children = Vec::with_capacity(child_count);
+        for _ in 0..child_count {
+            children.push(read_content(cursor, content_len)?);
+        }
+        Ok(OutlineNode::new(content, children, language))
+    }
+
+    fn decode_outline_nodes(
+        data: &[u8],
+        content_len: usize,
+    ) -> Result<Vec<OutlineNode>, OutlineCacheError> {
+        let mut cursor = Cursor::new(data);
+        let node_count = cursor.read_varint()? as usize;
+        let mut nodes = Vec::with_capacity(node_count);
+        for _ in 0..node_count {
+            nodes.push(read_node(&mut cursor, content_len)?);
+        }
+        Ok(nodes)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use crate::chunking::text_document::Position;
     use crate::chunking::text_document::Range;
 
-    use super::concat_documentation_string;
+    use super::{
+        attach_documentation_entries, bind_documentation_to_declarations, classify_comment,
+        comment_config_for_language, concat_documentation_string, symbol_tree, ClassInformation,
+        ClassNodeType, ClassWithFunctions, CommentKind, CommentShape, DocDirection,
+        DocumentationBinding, FunctionInformation, FunctionNodeInformation, FunctionNodeType,
+        OutlineNode, OutlineNodeType, TypeInformation, TypeNodeType,
+    };
+
+    #[test]
+    fn test_attach_documentation_entries_absorbs_attribute_between_doc_and_declaration() {
+        // line 0: doc comment
+        // line 1: #[derive(Debug)]
+        // line 2: fn foo() {}
+        let function = FunctionInformation::new(
+            Range::new(Position::new(2, 0, 20), Position::new(2, 12, 32)),
+            FunctionNodeType::Function,
+        );
+        let documentation_entries = vec![(
+            Range::new(Position::new(0, 0, 0), Position::new(0, 16, 16)),
+            "/// does a thing".to_owned(),
+        )];
+        let attribute_entries = vec![(
+            Range::new(Position::new(1, 0, 17), Position::new(1, 17, 17)),
+            "#[derive(Debug)]".to_owned(),
+        )];
+
+        let attached =
+            attach_documentation_entries(vec![function], documentation_entries, attribute_entries, "rust");
+
+        assert_eq!(attached.len(), 1);
+        assert_eq!(attached[0].range().start_byte(), 0);
+    }
+
+    #[test]
+    fn test_attach_documentation_entries_does_not_cross_into_a_sibling_declaration() {
+        // line 0: /// doc
+        // line 1: fn a() {}
+        // line 2: fn b() {}
+        // the doc on line 0 belongs to `fn a`; `fn b` must not also absorb it
+        // just because the blank-line tolerance alone would allow the jump.
+        let function_a = FunctionInformation::new(
+            Range::new(Position::new(1, 0, 17), Position::new(1, 10, 27)),
+            FunctionNodeType::Function,
+        );
+        let function_b = FunctionInformation::new(
+            Range::new(Position::new(2, 0, 28), Position::new(2, 10, 38)),
+            FunctionNodeType::Function,
+        );
+        let documentation_entries = vec![(
+            Range::new(Position::new(0, 0, 0), Position::new(0, 16, 16)),
+            "/// does a thing".to_owned(),
+        )];
+
+        let attached = attach_documentation_entries(
+            vec![function_a, function_b],
+            documentation_entries,
+            Vec::new(),
+            "rust",
+        );
+
+        assert_eq!(attached[0].range().start_byte(), 0);
+        assert_eq!(attached[1].range().start_byte(), 28);
+    }
+
+    #[test]
+    fn test_attach_documentation_entries_reaches_through_an_enclosing_block() {
+        // line 0: fn outer() {
+        // line 1:   /// inner doc
+        // line 2:   fn inner() {}
+        // line 3: }
+        // `inner`'s own doc comment must still attach even though `outer`
+        // (which contains it) ends on a much later line.
+        let outer = FunctionInformation::new(
+            Range::new(Position::new(0, 0, 0), Position::new(3, 1, 60)),
+            FunctionNodeType::Function,
+        );
+        let inner = FunctionInformation::new(
+            Range::new(Position::new(2, 2, 35), Position::new(2, 17, 50)),
+            FunctionNodeType::Function,
+        );
+        let documentation_entries = vec![(
+            Range::new(Position::new(1, 2, 15), Position::new(1, 14, 27)),
+            "/// inner doc".to_owned(),
+        )];
+
+        let attached =
+            attach_documentation_entries(vec![outer, inner], documentation_entries, Vec::new(), "rust");
+
+        assert_eq!(attached[1].range().start_byte(), 15);
+    }
+
+    #[test]
+    fn test_attach_documentation_entries_does_not_duplicate_an_enclosing_blocks_own_doc() {
+        // line 0: /// doc for outer
+        // line 1: fn outer() {
+        // line 2:   fn inner() {}
+        // line 3: }
+        // `inner` must not also absorb the doc that belongs to `outer`,
+        // even though the blank-line tolerance alone would bridge the gap.
+        let outer = FunctionInformation::new(
+            Range::new(Position::new(1, 0, 20), Position::new(3, 1, 60)),
+            FunctionNodeType::Function,
+        );
+        let inner = FunctionInformation::new(
+            Range::new(Position::new(2, 2, 35), Position::new(2, 17, 50)),
+            FunctionNodeType::Function,
+        );
+        let documentation_entries = vec![(
+            Range::new(Position::new(0, 0, 0), Position::new(0, 19, 19)),
+            "/// doc for outer".to_owned(),
+        )];
+
+        let attached =
+            attach_documentation_entries(vec![outer, inner], documentation_entries, Vec::new(), "rust");
+
+        assert_eq!(attached[0].range().start_byte(), 0);
+        assert_eq!(attached[1].range().start_byte(), 35);
+    }
+
+    #[test]
+    fn test_attach_documentation_entries_ignores_a_plain_comment() {
+        // line 0: // just a note, not a doc comment
+        // line 1: fn foo() {}
+        // a plain `//` comment is not a doc comment, so `fn foo` must keep
+        // its own start position rather than absorbing it
+        let function = FunctionInformation::new(
+            Range::new(Position::new(1, 0, 31), Position::new(1, 12, 43)),
+            FunctionNodeType::Function,
+        );
+        let documentation_entries = vec![(
+            Range::new(Position::new(0, 0, 0), Position::new(0, 30, 30)),
+            "// just a note, not a doc comment".to_owned(),
+        )];
+
+        let attached =
+            attach_documentation_entries(vec![function], documentation_entries, Vec::new(), "rust");
+
+        assert_eq!(attached[0].range().start_byte(), 31);
+    }
+
+    #[test]
+    fn test_attach_documentation_entries_ignores_an_inner_doc_comment() {
+        // line 0: //! documents the module, not `foo`
+        // line 1: fn foo() {}
+        // an inner doc comment documents the scope it's written inside of,
+        // not the declaration that happens to follow it
+        let function = FunctionInformation::new(
+            Range::new(Position::new(1, 0, 38), Position::new(1, 12, 50)),
+            FunctionNodeType::Function,
+        );
+        let documentation_entries = vec![(
+            Range::new(Position::new(0, 0, 0), Position::new(0, 37, 37)),
+            "//! documents the module, not `foo`".to_owned(),
+        )];
+
+        let attached =
+            attach_documentation_entries(vec![function], documentation_entries, Vec::new(), "rust");
+
+        assert_eq!(attached[0].range().start_byte(), 38);
+    }
+
+    #[test]
+    fn test_classify_comment_distinguishes_shape_and_direction() {
+        let rust = comment_config_for_language("rust");
+        assert_eq!(
+            classify_comment("/// outer doc", &rust),
+            CommentKind {
+                shape: CommentShape::Line,
+                doc: DocDirection::Outer,
+            }
+        );
+        assert_eq!(
+            classify_comment("//! inner doc", &rust),
+            CommentKind {
+                shape: CommentShape::Line,
+                doc: DocDirection::Inner,
+            }
+        );
+        assert_eq!(
+            classify_comment("//// not a doc", &rust),
+            CommentKind {
+                shape: CommentShape::Line,
+                doc: DocDirection::None,
+            }
+        );
+        assert_eq!(
+            classify_comment("// plain comment", &rust),
+            CommentKind {
+                shape: CommentShape::Line,
+                doc: DocDirection::None,
+            }
+        );
+        assert_eq!(
+            classify_comment("/** outer doc */", &rust),
+            CommentKind {
+                shape: CommentShape::Block,
+                doc: DocDirection::Outer,
+            }
+        );
+        assert_eq!(
+            classify_comment("/*! inner doc */", &rust),
+            CommentKind {
+                shape: CommentShape::Block,
+                doc: DocDirection::Inner,
+            }
+        );
+        assert_eq!(
+            classify_comment("/*** not a doc */", &rust),
+            CommentKind {
+                shape: CommentShape::Block,
+                doc: DocDirection::None,
+            }
+        );
+        assert_eq!(
+            classify_comment("/* plain comment */", &rust),
+            CommentKind {
+                shape: CommentShape::Block,
+                doc: DocDirection::None,
+            }
+        );
+    }
+
+    #[test]
+    fn test_classify_comment_recognises_a_python_docstring_and_hash_comment() {
+        let python = comment_config_for_language("python");
+        assert_eq!(
+            classify_comment("\"\"\"does a thing\"\"\"", &python),
+            CommentKind {
+                shape: CommentShape::Docstring,
+                doc: DocDirection::Inner,
+            }
+        );
+        assert_eq!(
+            classify_comment("# just a note", &python),
+            CommentKind {
+                shape: CommentShape::Line,
+                doc: DocDirection::None,
+            }
+        );
+    }
+
+    #[test]
+    fn test_classify_comment_treats_every_go_and_shell_line_comment_as_doc() {
+        let go = comment_config_for_language("golang");
+        assert_eq!(
+            classify_comment("// Frobnicate does a thing", &go),
+            CommentKind {
+                shape: CommentShape::Line,
+                doc: DocDirection::Outer,
+            }
+        );
+        let shell = comment_config_for_language("shell");
+        assert_eq!(
+            classify_comment("# does a thing", &shell),
+            CommentKind {
+                shape: CommentShape::Line,
+                doc: DocDirection::Outer,
+            }
+        );
+    }
+
+    #[test]
+    fn test_attach_documentation_entries_does_not_bridge_over_an_intervening_plain_comment() {
+        // line 0: /// doc for something earlier
+        // line 1: // unrelated TODO note
+        // line 2: fn foo() {}
+        // the plain comment on line 1 sits directly between the doc comment
+        // and `foo`, so `foo` must not absorb the doc - even though the doc
+        // comment's own end line would otherwise fall within the blank-line
+        // gap tolerance of `foo`'s start line.
+        let function = FunctionInformation::new(
+            Range::new(Position::new(2, 0, 57), Position::new(2, 12, 69)),
+            FunctionNodeType::Function,
+        );
+        let documentation_entries = vec![
+            (
+                Range::new(Position::new(0, 0, 0), Position::new(0, 28, 28)),
+                "/// doc for something earlier".to_owned(),
+            ),
+            (
+                Range::new(Position::new(1, 0, 29), Position::new(1, 24, 53)),
+                "// unrelated TODO note".to_owned(),
+            ),
+        ];
+
+        let attached =
+            attach_documentation_entries(vec![function], documentation_entries, Vec::new(), "rust");
+
+        assert_eq!(attached[0].range().start_byte(), 57);
+    }
+
+    #[test]
+    fn test_bind_documentation_to_declarations_binds_an_outer_block_to_the_next_declaration() {
+        // line 0: /// does a thing
+        // line 1: fn foo() {}
+        let documentation_entries = vec![(
+            Range::new(Position::new(0, 0, 0), Position::new(0, 16, 16)),
+            "does a thing".to_owned(),
+            CommentKind {
+                shape: CommentShape::Line,
+                doc: DocDirection::Outer,
+            },
+        )];
+        let declaration_ranges = vec![Range::new(
+            Position::new(1, 0, 17),
+            Position::new(1, 12, 29),
+        )];
+
+        let bound =
+            bind_documentation_to_declarations(documentation_entries, &declaration_ranges);
+
+        assert_eq!(bound.len(), 1);
+        match &bound[0].1 {
+            DocumentationBinding::Symbol(range) => assert_eq!(range.start_byte(), 17),
+            DocumentationBinding::FreeFloating => panic!("expected a symbol binding"),
+        }
+    }
+
+    #[test]
+    fn test_bind_documentation_to_declarations_binds_an_inner_block_to_the_enclosing_scope() {
+        // line 0: fn outer() {
+        // line 1:   //! documents outer, not a nested item
+        // line 2: }
+        let documentation_entries = vec![(
+            Range::new(Position::new(1, 2, 15), Position::new(1, 40, 53)),
+            "documents outer, not a nested item".to_owned(),
+            CommentKind {
+                shape: CommentShape::Line,
+                doc: DocDirection::Inner,
+            },
+        )];
+        let declaration_ranges = vec![Range::new(
+            Position::new(0, 0, 0),
+            Position::new(2, 1, 55),
+        )];
+
+        let bound =
+            bind_documentation_to_declarations(documentation_entries, &declaration_ranges);
+
+        assert_eq!(bound.len(), 1);
+        match &bound[0].1 {
+            DocumentationBinding::Symbol(range) => assert_eq!(range.start_byte(), 0),
+            DocumentationBinding::FreeFloating => panic!("expected a symbol binding"),
+        }
+    }
+
+    #[test]
+    fn test_bind_documentation_to_declarations_emits_a_license_banner_as_free_floating() {
+        // line 0: /// Copyright 2024, all rights reserved.
+        // (end of file - nothing left to document)
+        let documentation_entries = vec![(
+            Range::new(Position::new(0, 0, 0), Position::new(0, 40, 40)),
+            "Copyright 2024, all rights reserved.".to_owned(),
+            CommentKind {
+                shape: CommentShape::Line,
+                doc: DocDirection::Outer,
+            },
+        )];
+
+        let bound = bind_documentation_to_declarations(documentation_entries, &[]);
+
+        assert_eq!(bound.len(), 1);
+        assert!(matches!(bound[0].1, DocumentationBinding::FreeFloating));
+    }
+
+    #[test]
+    fn test_bind_documentation_to_declarations_drops_a_plain_comment_block() {
+        let documentation_entries = vec![(
+            Range::new(Position::new(0, 0, 0), Position::new(0, 20, 20)),
+            "just a note".to_owned(),
+            CommentKind {
+                shape: CommentShape::Line,
+                doc: DocDirection::None,
+            },
+        )];
+        let declaration_ranges = vec![Range::new(
+            Position::new(1, 0, 21),
+            Position::new(1, 12, 33),
+        )];
+
+        let bound =
+            bind_documentation_to_declarations(documentation_entries, &declaration_ranges);
+
+        assert!(bound.is_empty());
+    }
 
     #[test]
     fn test_documentation_string_concatenation() {
+        let doc_kind = CommentKind {
+            shape: CommentShape::Line,
+            doc: DocDirection::Outer,
+        };
         let documentation_strings = vec![
             (
                 Range::new(Position::new(0, 0, 0), Position::new(0, 0, 0)),
                 "first_comment".to_owned(),
+                doc_kind,
             ),
             (
                 Range::new(Position::new(1, 0, 0), Position::new(1, 0, 0)),
                 "second_comment".to_owned(),
+                doc_kind,
             ),
             (
                 Range::new(Position::new(4, 0, 0), Position::new(6, 0, 0)),
                 "third_multi_line_comment".to_owned(),
+                doc_kind,
             ),
             (
                 Range::new(Position::new(7, 0, 0), Position::new(7, 0, 0)),
                 "fourth_comment".to_owned(),
+                doc_kind,
             ),
         ];
-        let final_documentation_strings = concat_documentation_string(documentation_strings);
+        let final_documentation_strings =
+            concat_documentation_string(documentation_strings, &comment_config_for_language("rust"));
         assert_eq!(final_documentation_strings.len(), 2);
     }
+
+    #[test]
+    fn test_documentation_string_concatenation_preserves_a_blank_line_inside_a_run() {
+        // line 0: /// first paragraph
+        // line 1: //            <- a bare, non-doc separator line
+        // line 2: /// second paragraph
+        // the blank separator doesn't share the surrounding run's `Outer`
+        // `CommentKind`, but it carries no content of its own to misattach,
+        // so it rides along as a blank line rather than splitting the run.
+        let outer = CommentKind {
+            shape: CommentShape::Line,
+            doc: DocDirection::Outer,
+        };
+        let blank = CommentKind {
+            shape: CommentShape::Line,
+            doc: DocDirection::None,
+        };
+        let documentation_strings = vec![
+            (
+                Range::new(Position::new(0, 0, 0), Position::new(0, 0, 0)),
+                "first paragraph".to_owned(),
+                outer,
+            ),
+            (
+                Range::new(Position::new(1, 0, 0), Position::new(1, 0, 0)),
+                "".to_owned(),
+                blank,
+            ),
+            (
+                Range::new(Position::new(2, 0, 0), Position::new(2, 0, 0)),
+                "second paragraph".to_owned(),
+                outer,
+            ),
+        ];
+        let concatenated =
+            concat_documentation_string(documentation_strings, &comment_config_for_language("rust"));
+        assert_eq!(concatenated.len(), 1);
+        assert_eq!(concatenated[0].1, "first paragraph\n\nsecond paragraph");
+        assert_eq!(concatenated[0].2, outer);
+    }
+
+    #[test]
+    fn test_documentation_string_concatenation_never_fuses_across_a_one_line_code_gap() {
+        // line 0: /// first paragraph
+        // line 1: fn unrelated() {}   <- a line of code, not a blank comment
+        // line 2: /// second paragraph
+        // unlike a blank separator comment, a genuine source line in between
+        // must terminate the run rather than being bridged over.
+        let outer = CommentKind {
+            shape: CommentShape::Line,
+            doc: DocDirection::Outer,
+        };
+        let documentation_strings = vec![
+            (
+                Range::new(Position::new(0, 0, 0), Position::new(0, 0, 0)),
+                "first paragraph".to_owned(),
+                outer,
+            ),
+            (
+                Range::new(Position::new(2, 0, 0), Position::new(2, 0, 0)),
+                "second paragraph".to_owned(),
+                outer,
+            ),
+        ];
+        let concatenated =
+            concat_documentation_string(documentation_strings, &comment_config_for_language("rust"));
+        assert_eq!(concatenated.len(), 2);
+        assert_eq!(concatenated[0].1, "first paragraph");
+        assert_eq!(concatenated[1].1, "second paragraph");
+    }
+
+    #[test]
+    fn test_beautify_doc_string_strips_line_comment_markers() {
+        let rust = comment_config_for_language("rust");
+        assert_eq!(super::beautify_doc_string("/// does a thing", &rust), "does a thing");
+        assert_eq!(super::beautify_doc_string("//! module doc", &rust), "module doc");
+        assert_eq!(
+            super::beautify_doc_string("//no leading space", &rust),
+            "no leading space"
+        );
+    }
+
+    #[test]
+    fn test_signature_line_keeps_full_python_signature_with_type_annotated_parameters() {
+        let content = "def calculate(x: int, y: int) -> int:\n    return x + y";
+        assert_eq!(
+            OutlineNode::signature_line("python", content),
+            "def calculate(x: int, y: int) -> int: ..."
+        );
+    }
+
+    #[test]
+    fn test_beautify_doc_string_strips_block_comment_markers_and_asterisks() {
+        let block = "/**\n * does a thing\n * across two lines\n */";
+        assert_eq!(
+            super::beautify_doc_string(block, &comment_config_for_language("rust")),
+            "\ndoes a thing\nacross two lines"
+        );
+    }
+
+    #[test]
+    fn test_beautify_doc_string_keeps_literal_asterisk_in_single_line_block_comment() {
+        assert_eq!(
+            super::beautify_doc_string("/** * TODO */", &comment_config_for_language("rust")),
+            "* TODO"
+        );
+    }
+
+    #[test]
+    fn test_beautify_doc_string_strips_python_docstring_and_dedents() {
+        let python = comment_config_for_language("python");
+        let docstring = "\"\"\"\n    does a thing\n    across two lines\n    \"\"\"";
+        assert_eq!(
+            super::beautify_doc_string(docstring, &python),
+            "\ndoes a thing\nacross two lines\n"
+        );
+    }
+
+    #[test]
+    fn test_concat_documentation_string_preserves_relative_indentation_across_lines() {
+        // a doc comment's own `///` marker strip only removes one leading
+        // space per line (not a full dedent), so the 4-space markdown code
+        // block indentation on the second line survives merging with the
+        // first
+        let doc_kind = CommentKind {
+            shape: CommentShape::Line,
+            doc: DocDirection::Outer,
+        };
+        let entries = vec![
+            (
+                Range::new(Position::new(0, 0, 0), Position::new(0, 10, 10)),
+                "/// Usage:".to_owned(),
+                doc_kind,
+            ),
+            (
+                Range::new(Position::new(1, 0, 11), Position::new(1, 19, 30)),
+                "///     let x = 1;".to_owned(),
+                doc_kind,
+            ),
+        ];
+        let concatenated = concat_documentation_string(entries, &comment_config_for_language("rust"));
+        assert_eq!(concatenated.len(), 1);
+        assert_eq!(concatenated[0].1, "Usage:\n    let x = 1;");
+    }
+
+    #[test]
+    fn test_symbol_tree_nests_a_classs_methods_as_children() {
+        let class = ClassInformation::new(
+            Range::new(Position::new(0, 0, 0), Position::new(3, 1, 40)),
+            "Foo".to_owned(),
+            ClassNodeType::ClassDeclaration,
+        );
+        let mut method_a = FunctionInformation::new(
+            Range::new(Position::new(1, 4, 10), Position::new(1, 20, 26)),
+            FunctionNodeType::Function,
+        );
+        let mut node_information_a = FunctionNodeInformation::default();
+        node_information_a.set_name("method_a".to_owned());
+        method_a.set_node_information(node_information_a);
+
+        let mut method_b = FunctionInformation::new(
+            Range::new(Position::new(2, 4, 27), Position::new(2, 20, 39)),
+            FunctionNodeType::Function,
+        );
+        let mut node_information_b = FunctionNodeInformation::default();
+        node_information_b.set_name("method_b".to_owned());
+        method_b.set_node_information(node_information_b);
+
+        let group = ClassWithFunctions::class_functions(class, vec![method_a, method_b]);
+
+        let forest = symbol_tree(&[group], &[], &[]);
+
+        assert_eq!(forest.len(), 1);
+        let class_node = &forest[0];
+        assert_eq!(class_node.name(), "Foo");
+        assert_eq!(class_node.kind(), &OutlineNodeType::Class);
+        assert_eq!(class_node.children().len(), 2);
+        assert_eq!(class_node.children()[0].name(), "method_a");
+        assert_eq!(class_node.children()[0].kind(), &OutlineNodeType::Function);
+        assert_eq!(class_node.children()[1].name(), "method_b");
+    }
+
+    #[test]
+    fn test_symbol_tree_skips_function_captures_without_a_name() {
+        // a lone `identifier`/`parameters`/`body` capture for a function that
+        // never got a name attached isn't a complete symbol - it should not
+        // show up as a bare, nameless leaf.
+        let unnamed = FunctionInformation::new(
+            Range::new(Position::new(0, 0, 0), Position::new(0, 10, 10)),
+            FunctionNodeType::Function,
+        );
+        let identifier_only = FunctionInformation::new(
+            Range::new(Position::new(1, 0, 11), Position::new(1, 5, 16)),
+            FunctionNodeType::Identifier,
+        );
+
+        let forest = symbol_tree(&[], &[unnamed, identifier_only], &[]);
+        assert!(forest.is_empty());
+    }
+
+    #[test]
+    fn test_symbol_tree_includes_standalone_functions_and_types_as_leaves() {
+        let mut standalone = FunctionInformation::new(
+            Range::new(Position::new(0, 0, 0), Position::new(0, 10, 10)),
+            FunctionNodeType::Function,
+        );
+        let mut node_information = FunctionNodeInformation::default();
+        node_information.set_name("helper".to_owned());
+        standalone.set_node_information(node_information);
+
+        let type_information = TypeInformation::new(
+            Range::new(Position::new(1, 0, 11), Position::new(1, 20, 31)),
+            "Config".to_owned(),
+            TypeNodeType::TypeDeclaration,
+        );
+
+        let forest = symbol_tree(&[], &[standalone], &[type_information]);
+
+        assert_eq!(forest.len(), 2);
+        assert_eq!(forest[0].name(), "helper");
+        assert!(forest[0].children().is_empty());
+        assert_eq!(forest[1].name(), "Config");
+        assert_eq!(forest[1].kind(), &OutlineNodeType::TypeAlias);
+    }
 }
@@ -8,7 +8,7 @@ use std::{
 };
 
 use fancy_regex::Regex;
-use tree_sitter::Tree;
+use tree_sitter::{InputEdit, Point, Tree};
 
 use crate::{
     chunking::{
@@ -106,19 +106,43 @@ impl SnippetInformation {
     }
 }
 
+/// Builds the `n`-token windows (`n` = 2 for bigrams, 3 for trigrams) out of
+/// an ordered subtoken sequence, e.g. `["read", "file", "sync"]` with `n = 2`
+/// becomes `{"read file", "file sync"}`. Below `n` tokens yields no n-grams
+/// at all rather than a degenerate shorter one.
+fn ngrams(subtokens: &[String], n: usize) -> HashSet<String> {
+    if subtokens.len() < n {
+        return HashSet::new();
+    }
+    subtokens
+        .windows(n)
+        .map(|window| window.join(" "))
+        .collect()
+}
+
 /// This contains the bag of words for the given snippets and it uses a custom
-/// tokenizer to extract the words from the code
+/// tokenizer to extract the words from the code. Alongside the flat unigram
+/// set it also keeps the ordered bigrams and trigrams built from the same
+/// subtoken sequence, so similarity scoring can reward snippets that share
+/// phrases (`"read file"`, `"read file sync"`), not just loose words.
 #[derive(Debug)]
 pub struct BagOfWords {
     words: HashSet<String>,
+    bigrams: HashSet<String>,
+    trigrams: HashSet<String>,
     snippet: SnippetInformation,
 }
 
 impl BagOfWords {
     pub fn new(snippet_lines: Vec<String>, start_line: usize, end_line: usize) -> Self {
-        let bag_of_words = BagOfWords::tokenize_call(&snippet_lines.to_vec().join("\n"));
+        let subtokens = BagOfWords::tokenize_call(&snippet_lines.to_vec().join("\n"));
+        let words = subtokens.iter().cloned().collect();
+        let bigrams = ngrams(&subtokens, 2);
+        let trigrams = ngrams(&subtokens, 3);
         BagOfWords {
-            words: bag_of_words,
+            words,
+            bigrams,
+            trigrams,
             snippet: SnippetInformation::new(snippet_lines, start_line, end_line),
         }
     }
@@ -127,9 +151,13 @@ impl BagOfWords {
         token.len() > 1
     }
 
-    fn tokenize_call(code: &str) -> HashSet<String> {
+    /// Splits `code` into its subtokens (snake_case and camelCase/PascalCase
+    /// identifiers broken into their parts) in the order they occur, so
+    /// callers can both dedupe into a unigram set and build ordered n-grams
+    /// from the same pass.
+    fn tokenize_call(code: &str) -> Vec<String> {
         let re = Regex::new(r"\b\w+\b").unwrap();
-        let mut valid_tokens: HashSet<String> = Default::default();
+        let mut subtokens: Vec<String> = Vec::new();
 
         for m in re.find_iter(code) {
             let text = m.expect("to work").as_str();
@@ -139,7 +167,7 @@ impl BagOfWords {
                 let parts: Vec<&str> = text.split('_').collect();
                 for part in parts {
                     if BagOfWords::check_valid_token(part) {
-                        valid_tokens.insert(part.to_owned());
+                        subtokens.push(part.to_owned());
                     }
                 }
             } else if text.chars().any(|c| c.is_uppercase()) {
@@ -151,26 +179,252 @@ impl BagOfWords {
                     .collect();
                 for part in parts {
                     if BagOfWords::check_valid_token(part) {
-                        valid_tokens.insert(part.to_owned());
+                        subtokens.push(part.to_owned());
                     }
                 }
             } else {
                 if BagOfWords::check_valid_token(text) {
-                    valid_tokens.insert(text.to_owned());
+                    subtokens.push(text.to_owned());
                 }
             }
         }
 
-        // Now we want to create the bigrams and the tigrams from these tokens
-        // and have them stored too, so we can process them
-        valid_tokens
+        subtokens
     }
 
-    fn jaccard_score(&self, other: &Self) -> f32 {
-        let intersection_size = self.words.intersection(&other.words).count();
-        let union_size = self.words.len() + other.words.len() - intersection_size;
+    fn plain_jaccard(left: &HashSet<String>, right: &HashSet<String>) -> f32 {
+        if left.is_empty() && right.is_empty() {
+            return 0.0;
+        }
+        let intersection_size = left.intersection(right).count();
+        let union_size = left.len() + right.len() - intersection_size;
         intersection_size as f32 / union_size as f32
     }
+
+    /// Unigram Jaccard, but each term's contribution to the intersection and
+    /// union is weighted by `term_weights` instead of counting for 1, so
+    /// ubiquitous identifiers (`self`, `value`, `return`, ...) barely move
+    /// the score.
+    fn weighted_unigram_jaccard(&self, other: &Self, term_weights: &TermWeights) -> f32 {
+        let intersection_weight: f32 = self
+            .words
+            .intersection(&other.words)
+            .map(|term| term_weights.weight_of(term))
+            .sum();
+        let union_weight: f32 = self
+            .words
+            .union(&other.words)
+            .map(|term| term_weights.weight_of(term))
+            .sum();
+        if union_weight == 0.0 {
+            0.0
+        } else {
+            intersection_weight / union_weight
+        }
+    }
+
+    /// Combined similarity score: IDF-weighted unigram Jaccard plus the
+    /// bigram and trigram Jaccard scores scaled by `weights`, so snippets
+    /// sharing multi-token phrases rank above ones that merely share
+    /// common, unrelated words.
+    fn similarity_score(
+        &self,
+        other: &Self,
+        term_weights: &TermWeights,
+        weights: &SimilarityWeights,
+    ) -> f32 {
+        self.weighted_unigram_jaccard(other, term_weights)
+            + weights.bigram * Self::plain_jaccard(&self.bigrams, &other.bigrams)
+            + weights.trigram * Self::plain_jaccard(&self.trigrams, &other.trigrams)
+    }
+}
+
+/// Inverse-document-frequency weights for unigrams, computed once over the
+/// current set of window snippets (see
+/// `DocumentEditLines::snippets_using_sliding_window`). A term that shows up
+/// in every snippet (`self`, `value`, `return`, ...) gets a weight near the
+/// `+ 1.0` floor; a term unique to one snippet gets the full `ln(N)` boost.
+#[derive(Debug, Default, Clone)]
+struct TermWeights {
+    weight_by_term: HashMap<String, f32>,
+}
+
+impl TermWeights {
+    fn from_snippets(snippets: &[BagOfWords]) -> Self {
+        let snippet_count = snippets.len().max(1) as f32;
+        let mut document_frequency: HashMap<String, usize> = HashMap::new();
+        for snippet in snippets {
+            for term in &snippet.words {
+                *document_frequency.entry(term.clone()).or_insert(0) += 1;
+            }
+        }
+        let weight_by_term = document_frequency
+            .into_iter()
+            .map(|(term, document_frequency)| {
+                let weight = (snippet_count / document_frequency as f32).ln() + 1.0;
+                (term, weight)
+            })
+            .collect();
+        Self { weight_by_term }
+    }
+
+    fn weight_of(&self, term: &str) -> f32 {
+        self.weight_by_term.get(term).copied().unwrap_or(1.0)
+    }
+}
+
+/// Tunable coefficients for `DocumentEditLines::grab_similar_context`'s
+/// combined score: `weighted-unigram-Jaccard + bigram * bigram-Jaccard +
+/// trigram * trigram-Jaccard`. Exposed so callers can trade precision for
+/// recall instead of relying on a hardcoded cutoff.
+#[derive(Debug, Clone, Copy)]
+pub struct SimilarityWeights {
+    pub bigram: f32,
+    pub trigram: f32,
+}
+
+impl Default for SimilarityWeights {
+    fn default() -> Self {
+        SimilarityWeights {
+            bigram: 0.3,
+            trigram: 0.2,
+        }
+    }
+}
+
+/// Index of a [`RevisionNode`] in `DocumentEditLines::revisions`.
+pub type RevisionId = usize;
+
+/// One `remove_range` + `insert_at_position` pair, replayable through those
+/// same primitives: `range` is where it applies and `content` is what gets
+/// inserted there (after the range's existing content is removed).
+#[derive(Debug, Clone)]
+struct RevisionEdit {
+    range: Range,
+    content: String,
+}
+
+/// One node in the document's revision tree: the edit that produced this
+/// revision from `parent` (`forward`), the edit that reverts it back to
+/// `parent` (`inverse`), and the revisions made from here so far
+/// (`children`). A tree rather than a flat undo stack, so undoing and then
+/// making a different edit doesn't discard the branch you undid out of - it
+/// stays reachable, `redo` just stops pointing down into it.
+struct RevisionNode {
+    parent: Option<RevisionId>,
+    forward: RevisionEdit,
+    inverse: RevisionEdit,
+    children: Vec<RevisionId>,
+}
+
+/// The line terminator a [`DocumentLine`] was (or should be) joined with.
+/// Tracked per-line rather than once for the whole document so a file with
+/// mixed endings round-trips losslessly instead of being silently normalized
+/// to whichever style is more common.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum LineEnding {
+    Lf,
+    CrLf,
+}
+
+impl LineEnding {
+    fn as_str(&self) -> &'static str {
+        match self {
+            LineEnding::Lf => "\n",
+            LineEnding::CrLf => "\r\n",
+        }
+    }
+
+    fn byte_len(&self) -> usize {
+        self.as_str().len()
+    }
+
+    /// Whichever of `\n`/`\r\n` appears more often in `content`, defaulting
+    /// to the platform's native style when `content` has no line breaks at
+    /// all to judge from.
+    fn detect_dominant(content: &str) -> Self {
+        let crlf_count = content.matches("\r\n").count();
+        let lf_count = content.matches('\n').count().saturating_sub(crlf_count);
+        if crlf_count == 0 && lf_count == 0 {
+            if cfg!(windows) {
+                LineEnding::CrLf
+            } else {
+                LineEnding::Lf
+            }
+        } else if crlf_count >= lf_count {
+            LineEnding::CrLf
+        } else {
+            LineEnding::Lf
+        }
+    }
+
+    /// The ending each line of `content` was actually terminated with, in
+    /// order. The last entry (the file's final line, which has no
+    /// terminator of its own) is a don't-care and only present so the vector
+    /// lines up 1:1 with a per-line split of `content`.
+    fn per_line(content: &str) -> Vec<Self> {
+        content
+            .split('\n')
+            .map(|raw_line| {
+                if raw_line.ends_with('\r') {
+                    LineEnding::CrLf
+                } else {
+                    LineEnding::Lf
+                }
+            })
+            .collect()
+    }
+}
+
+/// How `Position`/`Range` columns passed in from (and handed back out to)
+/// the caller are counted. LSP speaks UTF-16 code units by default; internally
+/// every line is indexed by `char` (Rust's `str::chars`, i.e. Unicode scalar
+/// values - "UTF-32"), and nothing stops a caller from wanting plain UTF-8
+/// byte offsets either. Without this, a column is silently treated as a char
+/// count no matter where it actually came from, which corrupts edits on any
+/// line with multi-byte characters (emoji, CJK, accented text) before the
+/// edit point.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PositionEncoding {
+    Utf8,
+    Utf16,
+    Utf32,
+}
+
+impl PositionEncoding {
+    /// How many of this encoding's code units `character` takes up.
+    fn code_unit_width(&self, character: char) -> i64 {
+        match self {
+            PositionEncoding::Utf8 => character.len_utf8() as i64,
+            PositionEncoding::Utf16 => character.len_utf16() as i64,
+            PositionEncoding::Utf32 => 1,
+        }
+    }
+
+    /// Converts `column` (a count of this encoding's code units into `line`)
+    /// into the char index `DocumentEditLines` actually indexes with -
+    /// scanning `line`'s chars and summing each one's code-unit width until
+    /// `column` is reached. Out-of-range columns clamp to `line`'s length,
+    /// the same permissive behaviour the pre-encoding code had.
+    fn column_to_char_index(&self, line: &str, column: i64) -> usize {
+        let mut consumed = 0i64;
+        for (char_index, character) in line.chars().enumerate() {
+            if consumed >= column {
+                return char_index;
+            }
+            consumed += self.code_unit_width(character);
+        }
+        line.chars().count()
+    }
+
+    /// The inverse of `column_to_char_index`: the column, in this encoding,
+    /// that corresponds to `char_index` chars into `line`.
+    fn char_index_to_column(&self, line: &str, char_index: usize) -> i64 {
+        line.chars()
+            .take(char_index)
+            .map(|character| self.code_unit_width(character))
+            .sum()
+    }
 }
 
 /// Keeps track of the lines which have been added and edited into the code
@@ -184,6 +438,7 @@ pub enum DocumentLineStatus {
 pub struct DocumentLine {
     line_status: DocumentLineStatus,
     content: String,
+    line_ending: LineEnding,
 }
 
 impl DocumentLine {
@@ -215,6 +470,255 @@ pub struct DocumentEditLines {
     window_snippets: Vec<BagOfWords>,
     editor_parsing: Arc<EditorParsing>,
     tree: Option<Tree>,
+    // The document's dominant line ending, detected once at construction.
+    // `get_content` joins using each line's own `line_ending`, not this -
+    // this is the fallback used for freshly-created lines with nothing to
+    // detect from (an empty document, a brand new line typed at EOF, ...).
+    dominant_line_ending: LineEnding,
+    // How positions/ranges passed in (and handed back out) count columns.
+    // See `PositionEncoding` - everything in `lines` is always indexed by
+    // char regardless of this setting.
+    position_encoding: PositionEncoding,
+    // Revision history: `initial_content` is the document as constructed (the
+    // implicit root, revision `None`); `revisions` is the arena of edits made
+    // since then; `current_revision` is where we are in that tree right now;
+    // `root_revisions` holds the revisions with no parent, in the order they
+    // were created, so `redo()` has something to pick from once `undo()` has
+    // walked all the way back to `None`.
+    initial_content: String,
+    revisions: Vec<RevisionNode>,
+    current_revision: Option<RevisionId>,
+    root_revisions: Vec<RevisionId>,
+}
+
+/// One op in the edit script turning one char sequence into another, as
+/// produced by [`myers_diff`]: a run common to both, a run only in the old
+/// sequence (needs deleting), or a run only in the new one (needs inserting).
+#[derive(Debug, Clone)]
+enum DiffOp {
+    Equal(usize),
+    Delete(usize),
+    Insert(String),
+}
+
+/// Appends `op` onto `ops`, merging it into the last entry when they're the
+/// same kind, so a run of single-char steps out of the backtrack below
+/// collapses into one `Equal`/`Delete`/`Insert` instead of staying split.
+fn push_diff_op(ops: &mut Vec<DiffOp>, op: DiffOp) {
+    match (ops.last_mut(), op) {
+        (Some(DiffOp::Equal(count)), DiffOp::Equal(more)) => *count += more,
+        (Some(DiffOp::Delete(count)), DiffOp::Delete(more)) => *count += more,
+        (Some(DiffOp::Insert(text)), DiffOp::Insert(more)) => text.push_str(&more),
+        (_, op) => ops.push(op),
+    }
+}
+
+/// Char-level Myers shortest-edit-script diff - the same algorithm behind
+/// `git diff` and `diff-match-patch`. The common prefix/suffix is stripped
+/// first so only the genuinely-changed middle ever has to go through the
+/// O(ND) search below.
+fn myers_diff(old: &[char], new: &[char]) -> Vec<DiffOp> {
+    let prefix_len = old
+        .iter()
+        .zip(new.iter())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let old_rest = &old[prefix_len..];
+    let new_rest = &new[prefix_len..];
+    let suffix_len = old_rest
+        .iter()
+        .rev()
+        .zip(new_rest.iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let old_core = &old_rest[..old_rest.len() - suffix_len];
+    let new_core = &new_rest[..new_rest.len() - suffix_len];
+
+    let mut ops = Vec::new();
+    if prefix_len > 0 {
+        push_diff_op(&mut ops, DiffOp::Equal(prefix_len));
+    }
+    for op in myers_core(old_core, new_core) {
+        push_diff_op(&mut ops, op);
+    }
+    if suffix_len > 0 {
+        push_diff_op(&mut ops, DiffOp::Equal(suffix_len));
+    }
+    ops
+}
+
+/// The actual O(ND) Myers search + backtrack, run over the already-trimmed
+/// middle section where `a` and `b` share no leading or trailing char.
+/// See Eugene Myers' "An O(ND) Difference Algorithm and Its Variations".
+fn myers_core(a: &[char], b: &[char]) -> Vec<DiffOp> {
+    if a.is_empty() && b.is_empty() {
+        return Vec::new();
+    }
+    let n = a.len() as isize;
+    let m = b.len() as isize;
+    let max = (n + m) as usize;
+    let offset = max as isize;
+    let mut v = vec![0isize; 2 * max + 1];
+    let mut trace: Vec<Vec<isize>> = Vec::new();
+
+    'search: for d in 0..=max as isize {
+        trace.push(v.clone());
+        let mut k = -d;
+        while k <= d {
+            let idx = (k + offset) as usize;
+            let mut x = if k == -d || (k != d && v[idx - 1] < v[idx + 1]) {
+                v[idx + 1]
+            } else {
+                v[idx - 1] + 1
+            };
+            let mut y = x - k;
+            while x < n && y < m && a[x as usize] == b[y as usize] {
+                x += 1;
+                y += 1;
+            }
+            v[idx] = x;
+            if x >= n && y >= m {
+                break 'search;
+            }
+            k += 2;
+        }
+    }
+
+    let mut x = n;
+    let mut y = m;
+    let mut ops_rev: Vec<DiffOp> = Vec::new();
+    for d in (0..trace.len()).rev() {
+        let v = &trace[d];
+        let d = d as isize;
+        let k = x - y;
+        let idx = (k + offset) as usize;
+        let prev_k = if k == -d || (k != d && v[idx - 1] < v[idx + 1]) {
+            k + 1
+        } else {
+            k - 1
+        };
+        let prev_idx = (prev_k + offset) as usize;
+        let prev_x = v[prev_idx];
+        let prev_y = prev_x - prev_k;
+
+        while x > prev_x && y > prev_y {
+            ops_rev.push(DiffOp::Equal(1));
+            x -= 1;
+            y -= 1;
+        }
+        if d > 0 {
+            if x == prev_x {
+                ops_rev.push(DiffOp::Insert(b[prev_y as usize].to_string()));
+            } else {
+                ops_rev.push(DiffOp::Delete(1));
+            }
+        }
+        x = prev_x;
+        y = prev_y;
+    }
+    ops_rev.reverse();
+
+    let mut ops = Vec::new();
+    for op in ops_rev {
+        push_diff_op(&mut ops, op);
+    }
+    ops
+}
+
+/// Flushes a pending delete/insert (if any) into `edits` as one `(Range,
+/// String)` replacement spanning from where it started to the cursor's
+/// current position - zero-width when nothing was deleted (a pure insert).
+fn flush_pending_edit(
+    pending_start: &mut Option<(usize, i64, usize)>,
+    pending_insert: &mut String,
+    line: usize,
+    column: i64,
+    byte_offset: usize,
+    edits: &mut Vec<(Range, String)>,
+) {
+    let Some((start_line, start_column, start_byte)) = pending_start.take() else {
+        return;
+    };
+    let range = Range::new(
+        Position::new(start_line, start_column, start_byte),
+        Position::new(line, column, byte_offset),
+    );
+    edits.push((range, std::mem::take(pending_insert)));
+}
+
+/// A ```` ```lang ```` (or `~~~`) fenced code block found inside a document's
+/// content, together with the exact `Range` - spanning both the fences and
+/// the body - it came from, so it can be fed straight into `content_change`,
+/// linted, or handed to `EditorParsing` for language-aware follow-up.
+#[derive(Debug, Clone)]
+pub struct CodeBlock {
+    pub language: Option<String>,
+    pub code: String,
+    pub range: Range,
+}
+
+/// An opening fence line: how far it was indented, which character (`` ` ``
+/// or `~`) and how many of them opened it, and the info-string's first word
+/// (CommonMark's "language" for the block, if any).
+struct FenceOpen {
+    indent: usize,
+    fence_char: char,
+    fence_len: usize,
+    language: Option<String>,
+}
+
+/// Recognises `line` (terminator already stripped) as a fence opening -
+/// up to 3 spaces of indent, then 3-or-more backticks/tildes, then an
+/// optional info string. A backtick fence can't have a backtick in its info
+/// string (CommonMark forbids it, since it would be ambiguous with inline
+/// code); a tilde fence has no such restriction.
+fn parse_fence_open(line: &str) -> Option<FenceOpen> {
+    let indent = line.chars().take_while(|character| *character == ' ').count();
+    if indent > 3 {
+        return None;
+    }
+    let rest = &line[indent..];
+    let fence_char = rest.chars().next()?;
+    if fence_char != '`' && fence_char != '~' {
+        return None;
+    }
+    let fence_len = rest.chars().take_while(|character| *character == fence_char).count();
+    if fence_len < 3 {
+        return None;
+    }
+    let info = rest[fence_len..].trim();
+    if fence_char == '`' && info.contains('`') {
+        return None;
+    }
+    let language = info.split_whitespace().next().map(|token| token.to_string());
+    Some(FenceOpen {
+        indent,
+        fence_char,
+        fence_len,
+        language,
+    })
+}
+
+/// Strips up to `indent` leading spaces from `line` - the same indentation
+/// the opening fence itself carried, e.g. from being nested in a list item -
+/// so the block's `code` isn't left with that indentation baked in. A line
+/// indented less than the fence (it can't go further left) is left as-is.
+fn strip_up_to_indent(line: &str, indent: usize) -> String {
+    let strippable = line.chars().take_while(|character| *character == ' ').count().min(indent);
+    line.chars().skip(strippable).collect()
+}
+
+/// Recognises `line` as closing a fence opened with `fence_char`/`fence_len`:
+/// up to 3 spaces of indent, a run of at least `fence_len` of the same
+/// character, and nothing else (a closing fence carries no info string).
+fn is_fence_close(line: &str, fence_char: char, fence_len: usize) -> bool {
+    let indent = line.chars().take_while(|character| *character == ' ').count();
+    if indent > 3 {
+        return false;
+    }
+    let rest = &line[indent..];
+    let close_len = rest.chars().take_while(|character| *character == fence_char).count();
+    close_len >= fence_len && rest[close_len..].trim().is_empty()
 }
 
 impl DocumentEditLines {
@@ -223,25 +727,43 @@ impl DocumentEditLines {
         content: String,
         language: String,
         editor_parsing: Arc<EditorParsing>,
+        position_encoding: PositionEncoding,
     ) -> DocumentEditLines {
+        let dominant_line_ending = LineEnding::detect_dominant(&content);
         let mut document_lines = if content == "" {
             DocumentEditLines {
                 lines: vec![DocumentLine {
                     line_status: DocumentLineStatus::Unedited,
                     content: "".to_string(),
+                    line_ending: dominant_line_ending,
                 }],
                 file_path,
                 language,
                 window_snippets: vec![],
                 editor_parsing,
                 tree: None,
+                dominant_line_ending,
+                position_encoding,
+                initial_content: content,
+                revisions: Vec::new(),
+                current_revision: None,
+                root_revisions: Vec::new(),
             }
         } else {
+            let per_line_ending = LineEnding::per_line(&content);
             let lines = split_on_lines_editor_compatiable(&content)
                 .into_iter()
-                .map(|line_content| DocumentLine {
+                .enumerate()
+                .map(|(index, line_content)| DocumentLine {
                     line_status: DocumentLineStatus::Unedited,
-                    content: line_content.to_string(),
+                    // `line_content` still carries its own trailing `\r` (if
+                    // any) from the split above - strip it so `content` never
+                    // disagrees with `line_ending` about where the line ends.
+                    content: line_content.trim_end_matches('\r').to_string(),
+                    line_ending: per_line_ending
+                        .get(index)
+                        .copied()
+                        .unwrap_or(dominant_line_ending),
                 })
                 .collect::<Vec<_>>();
             DocumentEditLines {
@@ -251,25 +773,128 @@ impl DocumentEditLines {
                 window_snippets: vec![],
                 editor_parsing,
                 tree: None,
+                dominant_line_ending,
+                position_encoding,
+                initial_content: content,
+                revisions: Vec::new(),
+                current_revision: None,
+                root_revisions: Vec::new(),
             }
         };
         document_lines.set_tree();
         document_lines
     }
 
+    /// Reparses the document, reusing `self.tree` (already nudged by
+    /// [`Self::content_change`] via `Tree::edit`) as the base tree so
+    /// tree-sitter only has to rebuild the subtrees that actually changed,
+    /// instead of the whole file. Falls back to a full parse whenever there
+    /// is no previous tree to reuse (first parse, or a language without a
+    /// tree-sitter grammar).
     fn set_tree(&mut self) {
         if let Some(language_config) = self.editor_parsing.for_file_path(&self.file_path) {
-            let tree = language_config.get_tree_sitter_tree(self.get_content().as_bytes());
+            let source_code = self.get_content();
+            let tree = language_config
+                .get_tree_sitter_tree_with_old_tree(source_code.as_bytes(), self.tree.as_ref());
             self.tree = tree;
         }
     }
 
+    /// Converts a `(line, column)` position - `column` counted in
+    /// `self.position_encoding` - into the byte offset it points at within
+    /// `self.get_content()` (lines rejoined with each line's own
+    /// [`LineEnding`]). tree-sitter measures everything in bytes, so this
+    /// has to walk and re-encode rather than just summing `content.len()`.
+    fn byte_offset_for_position(&self, position: Position) -> Option<usize> {
+        let mut offset = 0usize;
+        for (index, line) in self.lines.iter().enumerate() {
+            if index == position.line() {
+                let char_index = self
+                    .position_encoding
+                    .column_to_char_index(&line.content, position.column());
+                let column_byte_offset: usize = line
+                    .content
+                    .chars()
+                    .take(char_index)
+                    .map(|character| character.len_utf8())
+                    .sum();
+                return Some(offset + column_byte_offset);
+            }
+            offset += line.content.len() + line.line_ending.byte_len();
+        }
+        None
+    }
+
+    /// Converts a `(line, column)` position - `column` counted in
+    /// `self.position_encoding` - into a `tree_sitter::Point`, whose `column`
+    /// is the byte offset into that row.
+    fn point_for_position(&self, position: Position) -> Option<Point> {
+        let line = self.lines.get(position.line())?;
+        let char_index = self
+            .position_encoding
+            .column_to_char_index(&line.content, position.column());
+        let column_byte_offset: usize = line
+            .content
+            .chars()
+            .take(char_index)
+            .map(|character| character.len_utf8())
+            .sum();
+        Some(Point {
+            row: position.line(),
+            column: column_byte_offset,
+        })
+    }
+
+    /// Where `start` ends up after `inserted` is typed at it, in tree-sitter
+    /// `Point` terms (row/byte-column).
+    fn point_after_insert(start: Point, inserted: &str) -> Point {
+        match inserted.rfind('\n') {
+            Some(last_newline_byte) => Point {
+                row: start.row + inserted.matches('\n').count(),
+                column: inserted.len() - last_newline_byte - 1,
+            },
+            None => Point {
+                row: start.row,
+                column: start.column + inserted.len(),
+            },
+        }
+    }
+
+    /// Computes the `tree_sitter::InputEdit` for replacing `range` with
+    /// `new_content`, measured against the document's current (pre-edit)
+    /// state. Returns `None` when any position in `range` can't be resolved
+    /// (e.g. it falls outside the document), in which case the caller should
+    /// fall back to a full reparse rather than feed tree-sitter a bogus edit.
+    fn compute_input_edit(&self, range: Range, new_content: &str) -> Option<InputEdit> {
+        let start_byte = self.byte_offset_for_position(range.start_position())?;
+        let old_end_byte = self.byte_offset_for_position(range.end_position())?;
+        let start_position = self.point_for_position(range.start_position())?;
+        let old_end_position = self.point_for_position(range.end_position())?;
+        let new_end_byte = start_byte + new_content.len();
+        let new_end_position = Self::point_after_insert(start_position, new_content);
+        Some(InputEdit {
+            start_byte,
+            old_end_byte,
+            new_end_byte,
+            start_position,
+            old_end_position,
+            new_end_position,
+        })
+    }
+
     pub fn get_content(&self) -> String {
-        self.lines
-            .iter()
-            .map(|line| line.content.clone())
-            .collect::<Vec<_>>()
-            .join("\n")
+        // Each line (other than the last, which has no terminator of its
+        // own) is rejoined with the ending it actually carries, so a
+        // mixed-ending or all-CRLF file round-trips losslessly instead of
+        // collapsing onto a single `"\n"`.
+        let mut content = String::new();
+        for (index, line) in self.lines.iter().enumerate() {
+            content.push_str(&line.content);
+            if index + 1 != self.lines.len() {
+                content.push_str(line.line_ending.as_str());
+            }
+        }
+        content
     }
 
     fn remove_range(&mut self, range: Range) {
@@ -297,12 +922,23 @@ impl DocumentEditLines {
                     range.end_column()
                 };
                 // we get the line at this line number and remove the content between the start and end columns
+                let line_content = self.lines[start_line].content.clone();
+                let start_index = self
+                    .position_encoding
+                    .column_to_char_index(&line_content, start_column);
+                // the exclusive end column is converted to a char index first
+                // and only then stepped back by one char, since the char before
+                // it can span more than one code unit
+                let end_index = if end_column == range.end_column() {
+                    self.position_encoding
+                        .column_to_char_index(&line_content, end_column)
+                } else {
+                    self.position_encoding
+                        .column_to_char_index(&line_content, range.end_column())
+                        .saturating_sub(1)
+                };
                 let line = self.lines.get_mut(start_line).unwrap();
-                let start_index = start_column;
-                let end_index = end_column;
                 let mut characters = line.content.chars().collect::<Vec<_>>();
-                let start_index = start_index as usize;
-                let end_index = end_index as usize;
                 dbg!("characters", &characters, start_index, end_index);
                 characters.drain(start_index..end_index + 1);
                 line.content = characters.into_iter().collect();
@@ -316,12 +952,19 @@ impl DocumentEditLines {
             // - merge the prefix and suffix of the start and end lines
 
             // get the start of line prefix
-            let start_line_characters = self.lines[start_line].content.chars().collect::<Vec<_>>();
-            let start_line_prefix = start_line_characters[..start_column as usize].to_owned();
+            let start_line_content = self.lines[start_line].content.clone();
+            let start_index = self
+                .position_encoding
+                .column_to_char_index(&start_line_content, start_column);
+            let start_line_characters = start_line_content.chars().collect::<Vec<_>>();
+            let start_line_prefix = start_line_characters[..start_index].to_owned();
             // get the end of line suffix
-            let end_column = range.end_column();
-            let end_line_characters = self.lines[end_line].content.chars().collect::<Vec<_>>();
-            let end_line_suffix = end_line_characters[end_column..].to_owned();
+            let end_line_content = self.lines[end_line].content.clone();
+            let end_index = self
+                .position_encoding
+                .column_to_char_index(&end_line_content, range.end_column());
+            let end_line_characters = end_line_content.chars().collect::<Vec<_>>();
+            let end_line_suffix = end_line_characters[end_index..].to_owned();
             {
                 let start_doc_line = self.lines.get_mut(start_line).unwrap();
                 start_doc_line.content = start_line_prefix.into_iter().collect::<String>()
@@ -341,27 +984,46 @@ impl DocumentEditLines {
         // when we want to insert at the position so first we try to start appending it at the line number from the current column
         // position and also add the suffix which we have, this way we get the new lines which need to be inserted
         let line_content = self.lines[position.line()].content.to_owned();
+        // The suffix carries the tail of the original line, so whichever new
+        // line it ends up on should keep that line's original ending rather
+        // than whatever `content` was typed with.
+        let original_line_ending = self.lines[position.line()].line_ending;
         let characters = line_content.chars().into_iter().collect::<Vec<_>>();
+        let char_index = self
+            .position_encoding
+            .column_to_char_index(&line_content, position.column());
         println!("characters: {:?}", characters);
         println!("position: {:?}", &position);
         // get the prefix right before the column position
-        let prefix = characters[..position.column() as usize]
+        let prefix = characters[..char_index]
             .to_owned()
             .into_iter()
             .collect::<String>();
         // get the suffix right after the column position
-        let suffix = characters[position.column() as usize..]
+        let suffix = characters[char_index..]
             .to_owned()
             .into_iter()
             .collect::<String>();
         // the new content here is the prefix + content + suffix
         let new_content = format!("{}{}{}", prefix.to_owned(), content, suffix);
+        // each split-out line's ending, in order, as it actually appeared in
+        // `new_content` - except the last, which inherits `suffix`'s (i.e.
+        // the original line's) ending rather than whatever `content` ended with
+        let mut new_line_endings = LineEnding::per_line(&new_content);
+        if let Some(last) = new_line_endings.last_mut() {
+            *last = original_line_ending;
+        }
         // now we get the new lines which need to be inserted
         let new_lines = split_on_lines_editor_compatiable(&new_content)
             .into_iter()
-            .map(|line| DocumentLine {
+            .enumerate()
+            .map(|(index, line)| DocumentLine {
                 line_status: DocumentLineStatus::Edited,
-                content: line.to_owned(),
+                content: line.trim_end_matches('\r').to_owned(),
+                line_ending: new_line_endings
+                    .get(index)
+                    .copied()
+                    .unwrap_or(self.dominant_line_ending),
             });
         // we also need to remove the line at the current line number
         self.lines.remove(position.line());
@@ -456,6 +1118,35 @@ impl DocumentEditLines {
 
     // If the contents have changed, we need to mark the new lines which have changed
     pub fn content_change(&mut self, range: Range, new_content: String) {
+        // Capture what's actually sitting in `range` before we remove it, so we
+        // can push a revision node whose inverse restores exactly this text.
+        let removed_content = self.text_in_range(range);
+        self.apply_edit(range, new_content.clone());
+        self.push_revision(range, new_content, removed_content);
+        // We want to get the code snippets here and make sure that the edited code snippets
+        // are together when creating the window
+        // TODO(skcd): Bring this back
+        // are we doing someting over here
+        dbg!("Generating snippets: {:?}", &self.file_path);
+        self.generate_snippets();
+    }
+
+    /// Replaces `range` with `new_content` against the tree and `self.lines`,
+    /// without touching the revision tree or regenerating snippets - the
+    /// building block `content_change` and `replace_content` both apply one
+    /// or more of, so a multi-edit overwrite can nudge the tree once per
+    /// edit (for an accurate incremental reparse) while still landing as a
+    /// single revision and a single `generate_snippets` call.
+    fn apply_edit(&mut self, range: Range, new_content: String) {
+        // Compute the edit in byte terms against the pre-edit document and nudge
+        // the existing tree with it before we touch `self.lines`, so `set_tree`
+        // (called from `generate_snippets`) can reparse incrementally instead
+        // of from scratch. If the byte math can't be resolved we just leave
+        // `self.tree` untouched and `set_tree` falls back to a full parse.
+        let input_edit = self.compute_input_edit(range, &new_content);
+        if let (Some(tree), Some(input_edit)) = (self.tree.as_mut(), input_edit) {
+            tree.edit(&input_edit);
+        }
         // First we remove the content at the range which is changing
         dbg!("Removing range: {:?}", &self.file_path);
         self.remove_range(range);
@@ -463,15 +1154,360 @@ impl DocumentEditLines {
         dbg!("Insert at position: {:?}", &self.file_path);
         // Then we insert the new content at the range
         self.insert_at_position(range.start_position(), new_content);
-        // We want to get the code snippets here and make sure that the edited code snippets
-        // are together when creating the window
-        // TODO(skcd): Bring this back
-        // are we doing someting over here
-        dbg!("Generating snippets: {:?}", &self.file_path);
+    }
+
+    /// Diffs `new_content` against `self.get_content()` at char granularity
+    /// (Myers' shortest-edit-script algorithm) and returns the smallest set
+    /// of `(Range, String)` replacements that turn the former into the
+    /// latter. Useful when a caller only has "old content -> new content"
+    /// (e.g. a full-file `didChange`) and would otherwise have to replace
+    /// the whole document through `content_change`, which is bad for undo
+    /// stacks, cursor preservation, and anyone shipping the edit over a wire.
+    pub fn edits_from_new_content(&self, new_content: &str) -> Vec<(Range, String)> {
+        let old_chars = self.get_content().chars().collect::<Vec<_>>();
+        let new_chars = new_content.chars().collect::<Vec<_>>();
+        let ops = myers_diff(&old_chars, &new_chars);
+
+        let mut edits = Vec::new();
+        let mut line = 0usize;
+        let mut column = 0i64;
+        let mut byte_offset = 0usize;
+        let mut pending_start: Option<(usize, i64, usize)> = None;
+        let mut pending_insert = String::new();
+        let mut old_index = 0usize;
+
+        for op in ops {
+            match op {
+                DiffOp::Equal(count) => {
+                    flush_pending_edit(
+                        &mut pending_start,
+                        &mut pending_insert,
+                        line,
+                        column,
+                        byte_offset,
+                        &mut edits,
+                    );
+                    for _ in 0..count {
+                        let character = old_chars[old_index];
+                        old_index += 1;
+                        byte_offset += character.len_utf8();
+                        if character == '\n' {
+                            line += 1;
+                            column = 0;
+                        } else {
+                            column += self.position_encoding.code_unit_width(character);
+                        }
+                    }
+                }
+                DiffOp::Delete(count) => {
+                    pending_start.get_or_insert((line, column, byte_offset));
+                    for _ in 0..count {
+                        let character = old_chars[old_index];
+                        old_index += 1;
+                        byte_offset += character.len_utf8();
+                        if character == '\n' {
+                            line += 1;
+                            column = 0;
+                        } else {
+                            column += self.position_encoding.code_unit_width(character);
+                        }
+                    }
+                }
+                DiffOp::Insert(text) => {
+                    pending_start.get_or_insert((line, column, byte_offset));
+                    pending_insert.push_str(&text);
+                }
+            }
+        }
+        flush_pending_edit(
+            &mut pending_start,
+            &mut pending_insert,
+            line,
+            column,
+            byte_offset,
+            &mut edits,
+        );
+        edits
+    }
+
+    /// Replaces the document's content with `new_content`, collapsing a
+    /// whole-document overwrite into a single revision/undo step instead of
+    /// one per changed region. The tree still only sees the minimal edits
+    /// `edits_from_new_content` computes - applied right-to-left, since each
+    /// one's `Range` is computed against the original content and applying a
+    /// later (further right) edit first means an earlier edit's range is
+    /// never invalidated by a shift in line/column numbers - so the
+    /// incremental reparse at the end only redoes the work the real change
+    /// touches, but callers still get one `undo()` back to the pre-replace
+    /// content rather than having to undo through every intermediate edit.
+    pub fn replace_content(&mut self, new_content: String) {
+        let old_content = self.get_content();
+        let old_end_position =
+            Self::position_after_insert(self.position_encoding, Position::new(0, 0, 0), old_content.clone());
+        let full_range = Range::new(Position::new(0, 0, 0), old_end_position);
+
+        let edits = self.edits_from_new_content(&new_content);
+        for (range, content) in edits.into_iter().rev() {
+            self.apply_edit(range, content);
+        }
+        self.push_revision(full_range, new_content, old_content);
         self.generate_snippets();
     }
 
-    pub fn grab_similar_context(&self, context: &str) -> Vec<SnippetInformation> {
+    /// Finds every fenced code block in `get_content()`, walking line-by-line
+    /// and tracking `(line, column, byte_offset)` the same way `Position`
+    /// does. An opening fence (``` or ~~~, optionally indented, with an
+    /// optional language after it) starts a block; it ends at the first
+    /// line that closes with a fence of the same character at least as long,
+    /// or at EOF if none ever comes (an unterminated block is still a block,
+    /// not a parse error). `range` spans from the start of the opening
+    /// fence's line to the start of the line right after the closing fence
+    /// (or to the end of the document, for an unterminated block), so the
+    /// whole thing - fences included - can be sliced back out or replaced.
+    pub fn fenced_code_blocks(&self) -> Vec<CodeBlock> {
+        let content = self.get_content();
+        let mut physical_lines = Vec::new();
+        let mut byte_offset = 0usize;
+        for raw_line in content.split_inclusive('\n') {
+            physical_lines.push((raw_line, byte_offset));
+            byte_offset += raw_line.len();
+        }
+        let total_bytes = byte_offset;
+
+        let mut blocks = Vec::new();
+        let mut line_index = 0usize;
+        while line_index < physical_lines.len() {
+            let (raw_line, line_byte_start) = physical_lines[line_index];
+            let text = raw_line.trim_end_matches(['\n', '\r']);
+            let Some(opening) = parse_fence_open(text) else {
+                line_index += 1;
+                continue;
+            };
+            let start_position = Position::new(line_index, 0, line_byte_start);
+
+            let mut code_lines = Vec::new();
+            let mut closing_line_index = None;
+            let mut search_index = line_index + 1;
+            while search_index < physical_lines.len() {
+                let (raw_candidate, _) = physical_lines[search_index];
+                let candidate_text = raw_candidate.trim_end_matches(['\n', '\r']);
+                if is_fence_close(candidate_text, opening.fence_char, opening.fence_len) {
+                    closing_line_index = Some(search_index);
+                    break;
+                }
+                code_lines.push(strip_up_to_indent(candidate_text, opening.indent));
+                search_index += 1;
+            }
+
+            let (end_position, next_line_index) = match closing_line_index {
+                Some(closing_index) => {
+                    let next_index = closing_index + 1;
+                    let end_byte = physical_lines
+                        .get(next_index)
+                        .map(|(_, start)| *start)
+                        .unwrap_or(total_bytes);
+                    (Position::new(next_index, 0, end_byte), next_index)
+                }
+                None => {
+                    let last_index = physical_lines.len() - 1;
+                    let last_text = physical_lines[last_index].0.trim_end_matches(['\n', '\r']);
+                    let end_column = self
+                        .position_encoding
+                        .char_index_to_column(last_text, last_text.chars().count());
+                    (
+                        Position::new(last_index, end_column, total_bytes),
+                        physical_lines.len(),
+                    )
+                }
+            };
+
+            blocks.push(CodeBlock {
+                language: opening.language,
+                code: code_lines.join("\n"),
+                range: Range::new(start_position, end_position),
+            });
+            line_index = next_line_index;
+        }
+        blocks
+    }
+
+    /// The exact text `remove_range(range)` would delete, read out before the
+    /// deletion happens - used to build the inverse edit for undo.
+    fn text_in_range(&self, range: Range) -> String {
+        let start_line = range.start_line();
+        let end_line = range.end_line();
+        if start_line == end_line {
+            let line_content = &self.lines[start_line].content;
+            let characters = line_content.chars().collect::<Vec<_>>();
+            let end_column = self
+                .position_encoding
+                .column_to_char_index(line_content, range.end_column())
+                .min(characters.len());
+            let start_column = self
+                .position_encoding
+                .column_to_char_index(line_content, range.start_column())
+                .min(end_column);
+            characters[start_column..end_column].iter().collect()
+        } else {
+            let mut removed = String::new();
+            let start_line_content = &self.lines[start_line].content;
+            let start_characters = start_line_content.chars().collect::<Vec<_>>();
+            let start_column = self
+                .position_encoding
+                .column_to_char_index(start_line_content, range.start_column())
+                .min(start_characters.len());
+            removed.extend(&start_characters[start_column..]);
+            for line_index in start_line + 1..end_line {
+                removed.push_str(self.lines[line_index - 1].line_ending.as_str());
+                removed.push_str(&self.lines[line_index].content);
+            }
+            removed.push_str(self.lines[end_line - 1].line_ending.as_str());
+            let end_line_content = &self.lines[end_line].content;
+            let end_characters = end_line_content.chars().collect::<Vec<_>>();
+            let end_column = self
+                .position_encoding
+                .column_to_char_index(end_line_content, range.end_column())
+                .min(end_characters.len());
+            removed.extend(&end_characters[..end_column]);
+            removed
+        }
+    }
+
+    /// Where `start` ends up, in `position_encoding`-column `Position` terms,
+    /// after `inserted` is typed at it.
+    fn position_after_insert(
+        encoding: PositionEncoding,
+        start: Position,
+        inserted: String,
+    ) -> Position {
+        match inserted.rfind('\n') {
+            Some(_) => {
+                let line_count = inserted.matches('\n').count();
+                let last_line = inserted.rsplit('\n').next().unwrap_or("");
+                let last_line_column =
+                    encoding.char_index_to_column(last_line, last_line.chars().count());
+                Position::new(start.line() + line_count, last_line_column, 0)
+            }
+            None => {
+                let inserted_column =
+                    encoding.char_index_to_column(&inserted, inserted.chars().count());
+                Position::new(start.line(), start.column() + inserted_column, 0)
+            }
+        }
+    }
+
+    /// Records the edit just applied through `remove_range`/`insert_at_position`
+    /// as a new revision: `forward` replays it, `inverse` undoes it by putting
+    /// `removed_content` back where `new_content` now sits.
+    fn push_revision(&mut self, range: Range, new_content: String, removed_content: String) {
+        let inserted_end_position = Self::position_after_insert(
+            self.position_encoding,
+            range.start_position(),
+            new_content.clone(),
+        );
+        let forward = RevisionEdit {
+            range,
+            content: new_content,
+        };
+        let inverse = RevisionEdit {
+            range: Range::new(range.start_position(), inserted_end_position),
+            content: removed_content,
+        };
+        let parent = self.current_revision;
+        let new_revision_id = self.revisions.len();
+        self.revisions.push(RevisionNode {
+            parent,
+            forward,
+            inverse,
+            children: Vec::new(),
+        });
+        match parent {
+            Some(parent_id) => self.revisions[parent_id].children.push(new_revision_id),
+            None => self.root_revisions.push(new_revision_id),
+        }
+        self.current_revision = Some(new_revision_id);
+    }
+
+    /// Reverts the most recent edit by replaying its stored inverse, and
+    /// moves `current_revision` back to its parent. Returns `false` (and
+    /// does nothing) if there is nothing left to undo.
+    pub fn undo(&mut self) -> bool {
+        let Some(revision_id) = self.current_revision else {
+            return false;
+        };
+        let inverse = self.revisions[revision_id].inverse.clone();
+        self.apply_edit(inverse.range, inverse.content);
+        self.current_revision = self.revisions[revision_id].parent;
+        self.generate_snippets();
+        true
+    }
+
+    /// Re-applies the edit most recently undone out of, by replaying its
+    /// stored forward edit. When the current revision has more than one
+    /// child (an undo was followed by a different edit), the most recently
+    /// created branch is the one redone. Returns `false` if there is nothing
+    /// to redo.
+    pub fn redo(&mut self) -> bool {
+        let next_revision = match self.current_revision {
+            Some(revision_id) => self.revisions[revision_id].children.last().copied(),
+            None => self.root_revisions.last().copied(),
+        };
+        let Some(next_revision) = next_revision else {
+            return false;
+        };
+        let forward = self.revisions[next_revision].forward.clone();
+        self.apply_edit(forward.range, forward.content);
+        self.current_revision = Some(next_revision);
+        self.generate_snippets();
+        true
+    }
+
+    /// The path of revisions from the root down to (and including)
+    /// `revision_id`.
+    fn path_from_root(&self, revision_id: RevisionId) -> Vec<RevisionId> {
+        let mut path = Vec::new();
+        let mut current = Some(revision_id);
+        while let Some(id) = current {
+            path.push(id);
+            current = self.revisions[id].parent;
+        }
+        path.reverse();
+        path
+    }
+
+    /// Reconstructs the document content as it was at `revision_id` (`None`
+    /// meaning the content the document was constructed with), by replaying
+    /// every forward edit from the root onto a scratch copy rather than
+    /// mutating `self`.
+    pub fn content_at_revision(&self, revision_id: Option<RevisionId>) -> String {
+        let Some(revision_id) = revision_id else {
+            return self.initial_content.clone();
+        };
+        let mut scratch = DocumentEditLines::new(
+            self.file_path.clone(),
+            self.initial_content.clone(),
+            self.language.clone(),
+            self.editor_parsing.clone(),
+            self.position_encoding,
+        );
+        for id in self.path_from_root(revision_id) {
+            let forward = self.revisions[id].forward.clone();
+            scratch.remove_range(forward.range);
+            scratch.insert_at_position(forward.range.start_position(), forward.content);
+        }
+        scratch.get_content()
+    }
+
+    /// `weights` tunes how much bigram/trigram overlap adds on top of the
+    /// IDF-weighted unigram score; `threshold` replaces the old hardcoded
+    /// `0.3` cutoff; `max_snippets` replaces the old hardcoded `truncate(10)`.
+    pub fn grab_similar_context(
+        &self,
+        context: &str,
+        weights: SimilarityWeights,
+        threshold: f32,
+        max_snippets: usize,
+    ) -> Vec<SnippetInformation> {
         // go through all the snippets and see which ones are similar to the context
         let bag_of_words = BagOfWords::new(
             context
@@ -482,12 +1518,13 @@ impl DocumentEditLines {
             0,
             0,
         );
+        let term_weights = TermWeights::from_snippets(&self.window_snippets);
         let mut scored_snippets = self
             .window_snippets
             .iter()
             .filter_map(|snippet| {
-                let score = snippet.jaccard_score(&bag_of_words);
-                if score > 0.3 {
+                let score = snippet.similarity_score(&bag_of_words, &term_weights, &weights);
+                if score > threshold {
                     Some((score, snippet))
                 } else {
                     None
@@ -496,9 +1533,9 @@ impl DocumentEditLines {
             .collect::<Vec<_>>();
         // f32 comparison should work
         scored_snippets.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap());
-        // we take at the very most 10 snippets from a single file
+        // we take at the very most `max_snippets` snippets from a single file
         // this prevents a single file from giving out too much data
-        scored_snippets.truncate(10);
+        scored_snippets.truncate(max_snippets);
 
         scored_snippets
             .into_iter()
@@ -516,7 +1553,9 @@ mod tests {
         text_document::{Position, Range},
     };
 
-    use super::DocumentEditLines;
+    use super::{
+        ngrams, BagOfWords, DocumentEditLines, PositionEncoding, SimilarityWeights, TermWeights,
+    };
 
     #[test]
     fn test_document_lines_works() {
@@ -530,6 +1569,7 @@ mod tests {
             .to_owned(),
             "".to_owned(),
             editor_parsing,
+            PositionEncoding::Utf32,
         );
         assert_eq!(document.lines.len(), 4);
     }
@@ -548,6 +1588,7 @@ SIXTH LINE 🫡🚀"#
                 .to_owned(),
             "".to_owned(),
             editor_parsing,
+            PositionEncoding::Utf32,
         );
         let range = Range::new(Position::new(4, 0, 0), Position::new(5, 0, 0));
         document.remove_range(range);
@@ -570,6 +1611,7 @@ SIXTH LINE 🫡🚀"#
             r#"SOMETHING"#.to_owned(),
             "".to_owned(),
             editor_parsing,
+            PositionEncoding::Utf32,
         );
         let range = Range::new(Position::new(0, 0, 0), Position::new(0, 0, 0));
         document.remove_range(range);
@@ -591,6 +1633,7 @@ SIXTH LINE 🫡🚀"#
                 .to_owned(),
             "".to_owned(),
             editor_parsing,
+            PositionEncoding::Utf32,
         );
         let position = Position::new(3, 1, 0);
         document.insert_at_position(position, "🚀🚀🚀\n🪨🪨".to_owned());
@@ -610,8 +1653,13 @@ SIXTH LINE 🫡🚀"#
     #[test]
     fn test_insert_on_empty_document_works() {
         let editor_parsing = Arc::new(EditorParsing::default());
-        let mut document =
-            DocumentEditLines::new("".to_owned(), "".to_owned(), "".to_owned(), editor_parsing);
+        let mut document = DocumentEditLines::new(
+            "".to_owned(),
+            "".to_owned(),
+            "".to_owned(),
+            editor_parsing,
+            PositionEncoding::Utf32,
+        );
         let position = Position::new(0, 0, 0);
         document.insert_at_position(position, "SOMETHING".to_owned());
         let updated_content = document.get_content();
@@ -632,6 +1680,7 @@ SIXTH LINE 🫡🚀"#
                 .to_owned(),
             "".to_owned(),
             editor_parsing,
+            PositionEncoding::Utf32,
         );
         let range = Range::new(Position::new(0, 0, 0), Position::new(5, 13, 0));
         document.remove_range(range);
@@ -647,6 +1696,7 @@ SIXTH LINE 🫡🚀"#
             "blah blah\n// bbbbbbbb\nblah blah".to_owned(),
             "".to_owned(),
             editor_parsing,
+            PositionEncoding::Utf32,
         );
         let range = Range::new(Position::new(1, 3, 0), Position::new(1, 11, 0));
         document.remove_range(range);
@@ -678,6 +1728,7 @@ fff"#
                 .to_owned(),
             "".to_owned(),
             editor_parsing,
+            PositionEncoding::Utf32,
         );
         let range = Range::new(Position::new(9, 0, 0), Position::new(13, 0, 0));
         document.content_change(range, "".to_owned());
@@ -716,6 +1767,7 @@ fff"#;
             original_content.to_owned(),
             "".to_owned(),
             Arc::new(EditorParsing::default()),
+            PositionEncoding::Utf32,
         );
         let range = Range::new(Position::new(6, 0, 0), Position::new(8, 2, 0));
         document_lines.content_change(range, "expected_output".to_owned());
@@ -732,4 +1784,434 @@ expected_output
 fff"#;
         assert_eq!(updated_content, expected_output);
     }
+
+    #[test]
+    fn test_utf8_encoding_keeps_byte_columns_aligned_on_multibyte_line() {
+        let editor_parsing = Arc::new(EditorParsing::default());
+        // "café 🚀 blah" - `é` is 2 bytes, `🚀` is 4 bytes, so byte and char
+        // columns diverge after each of them.
+        let mut document = DocumentEditLines::new(
+            "".to_owned(),
+            "café 🚀 blah".to_owned(),
+            "".to_owned(),
+            editor_parsing,
+            PositionEncoding::Utf8,
+        );
+        // Byte column 6 is right after `café ` (c-a-f-0xC3-0xA9-space = 6 bytes,
+        // so column 6 lands right before the rocket).
+        let position = Position::new(0, 6, 0);
+        document.insert_at_position(position, "big ".to_owned());
+        let updated_content = document.get_content();
+        assert_eq!(updated_content, "café big 🚀 blah");
+    }
+
+    #[test]
+    fn test_utf16_encoding_removes_range_spanning_surrogate_pair() {
+        let editor_parsing = Arc::new(EditorParsing::default());
+        // 🚀 is a single UTF-16 surrogate pair (2 code units), unlike `café`
+        // which is plain BMP. `café 🚀 blah` in UTF-16 columns: c(0) a(1) f(2)
+        // é(3) space(4) 🚀(5..7) space(7) b(8) l(9) a(10) h(11).
+        let mut document = DocumentEditLines::new(
+            "".to_owned(),
+            "café 🚀 blah".to_owned(),
+            "".to_owned(),
+            editor_parsing,
+            PositionEncoding::Utf16,
+        );
+        let range = Range::new(Position::new(0, 5, 0), Position::new(0, 8, 0));
+        document.remove_range(range);
+        let updated_content = document.get_content();
+        assert_eq!(updated_content, "café blah");
+    }
+
+    #[test]
+    fn test_fenced_code_blocks_extracts_language_and_body() {
+        let editor_parsing = Arc::new(EditorParsing::default());
+        let content = r#"Some text before.
+
+```rust
+fn main() {}
+```
+
+Some text after."#;
+        let document = DocumentEditLines::new(
+            "".to_owned(),
+            content.to_owned(),
+            "".to_owned(),
+            editor_parsing,
+            PositionEncoding::Utf32,
+        );
+        let blocks = document.fenced_code_blocks();
+        assert_eq!(blocks.len(), 1);
+        assert_eq!(blocks[0].language.as_deref(), Some("rust"));
+        assert_eq!(blocks[0].code, "fn main() {}");
+        assert_eq!(blocks[0].range.start_line(), 2);
+        assert_eq!(blocks[0].range.end_line(), 5);
+    }
+
+    #[test]
+    fn test_fenced_code_blocks_handles_tilde_fence_and_no_language() {
+        let editor_parsing = Arc::new(EditorParsing::default());
+        let content = "~~~~\nplain text\n~~~~\n";
+        let document = DocumentEditLines::new(
+            "".to_owned(),
+            content.to_owned(),
+            "".to_owned(),
+            editor_parsing,
+            PositionEncoding::Utf32,
+        );
+        let blocks = document.fenced_code_blocks();
+        assert_eq!(blocks.len(), 1);
+        assert_eq!(blocks[0].language, None);
+        assert_eq!(blocks[0].code, "plain text");
+    }
+
+    #[test]
+    fn test_fenced_code_blocks_treats_eof_as_close_for_unterminated_block() {
+        let editor_parsing = Arc::new(EditorParsing::default());
+        let content = "```python\nprint(1)\nprint(2)";
+        let document = DocumentEditLines::new(
+            "".to_owned(),
+            content.to_owned(),
+            "".to_owned(),
+            editor_parsing,
+            PositionEncoding::Utf32,
+        );
+        let blocks = document.fenced_code_blocks();
+        assert_eq!(blocks.len(), 1);
+        assert_eq!(blocks[0].language.as_deref(), Some("python"));
+        assert_eq!(blocks[0].code, "print(1)\nprint(2)");
+    }
+
+    #[test]
+    fn test_fenced_code_blocks_finds_multiple_indented_blocks() {
+        let editor_parsing = Arc::new(EditorParsing::default());
+        let content = "- item one\n  ```js\n  console.log(1)\n  ```\n- item two\n\n```js\nconsole.log(2)\n```\n";
+        let document = DocumentEditLines::new(
+            "".to_owned(),
+            content.to_owned(),
+            "".to_owned(),
+            editor_parsing,
+            PositionEncoding::Utf32,
+        );
+        let blocks = document.fenced_code_blocks();
+        assert_eq!(blocks.len(), 2);
+        assert_eq!(blocks[0].code, "console.log(1)");
+        assert_eq!(blocks[1].code, "console.log(2)");
+    }
+
+    #[test]
+    fn test_edits_from_new_content_is_empty_when_content_is_unchanged() {
+        let editor_parsing = Arc::new(EditorParsing::default());
+        let document = DocumentEditLines::new(
+            "".to_owned(),
+            "FIRST LINE\nSECOND LINE".to_owned(),
+            "".to_owned(),
+            editor_parsing,
+            PositionEncoding::Utf32,
+        );
+        assert_eq!(
+            document.edits_from_new_content("FIRST LINE\nSECOND LINE"),
+            vec![]
+        );
+    }
+
+    #[test]
+    fn test_edits_from_new_content_yields_a_zero_width_range_for_a_pure_insert() {
+        let editor_parsing = Arc::new(EditorParsing::default());
+        let document = DocumentEditLines::new(
+            "".to_owned(),
+            "FIRST LINE\nTHIRD LINE".to_owned(),
+            "".to_owned(),
+            editor_parsing,
+            PositionEncoding::Utf32,
+        );
+        let edits = document.edits_from_new_content("FIRST LINE\nSECOND LINE\nTHIRD LINE");
+        assert_eq!(edits.len(), 1);
+        let (range, replacement) = &edits[0];
+        assert_eq!(range.start_position(), range.end_position());
+        assert_eq!(replacement, "SECOND LINE\n");
+    }
+
+    #[test]
+    fn test_edits_from_new_content_covers_a_pure_delete_with_an_empty_replacement() {
+        let editor_parsing = Arc::new(EditorParsing::default());
+        let document = DocumentEditLines::new(
+            "".to_owned(),
+            "FIRST LINE\nSECOND LINE\nTHIRD LINE".to_owned(),
+            "".to_owned(),
+            editor_parsing,
+            PositionEncoding::Utf32,
+        );
+        let edits = document.edits_from_new_content("FIRST LINE\nTHIRD LINE");
+        assert_eq!(edits.len(), 1);
+        let (range, replacement) = &edits[0];
+        assert_ne!(range.start_position(), range.end_position());
+        assert_eq!(replacement, "");
+    }
+
+    #[test]
+    fn test_replace_content_updates_the_document_to_the_new_content() {
+        let editor_parsing = Arc::new(EditorParsing::default());
+        let mut document = DocumentEditLines::new(
+            "".to_owned(),
+            "FIRST LINE\nSECOND LINE\nTHIRD LINE".to_owned(),
+            "".to_owned(),
+            editor_parsing,
+            PositionEncoding::Utf32,
+        );
+        document.replace_content("FIRST LINE\nCHANGED LINE\nTHIRD LINE".to_owned());
+        assert_eq!(
+            document.get_content(),
+            "FIRST LINE\nCHANGED LINE\nTHIRD LINE"
+        );
+    }
+
+    #[test]
+    fn test_replace_content_undoes_in_a_single_step() {
+        let editor_parsing = Arc::new(EditorParsing::default());
+        let mut document = DocumentEditLines::new(
+            "".to_owned(),
+            "FIRST LINE\nSECOND LINE\nTHIRD LINE".to_owned(),
+            "".to_owned(),
+            editor_parsing,
+            PositionEncoding::Utf32,
+        );
+        document.replace_content("FIRST LINE\nCHANGED LINE\nTHIRD LINE".to_owned());
+        assert!(document.undo());
+        assert_eq!(
+            document.get_content(),
+            "FIRST LINE\nSECOND LINE\nTHIRD LINE"
+        );
+        // the whole overwrite was one revision, so there's nothing left to undo into
+        assert!(!document.undo());
+    }
+
+    #[test]
+    fn test_compute_input_edit_measures_bytes_and_points_against_the_pre_edit_content() {
+        let editor_parsing = Arc::new(EditorParsing::default());
+        // "café" puts a 2-byte char before the edit point so byte offsets and
+        // char/column counts would diverge if this fell back to char counting.
+        let document = DocumentEditLines::new(
+            "".to_owned(),
+            "café\nsecond line".to_owned(),
+            "".to_owned(),
+            editor_parsing,
+            PositionEncoding::Utf32,
+        );
+        let range = Range::new(Position::new(1, 0, 0), Position::new(1, 6, 0));
+        let input_edit = document
+            .compute_input_edit(range, "changed")
+            .expect("both positions resolve");
+        // "café\n" is 6 bytes (c-a-f-0xC3-0xA9-\n), so the second line starts
+        // at byte 6 no matter how many chars `café` took to get there.
+        assert_eq!(input_edit.start_byte, 6);
+        assert_eq!(input_edit.old_end_byte, 12);
+        assert_eq!(input_edit.new_end_byte, 6 + "changed".len());
+        assert_eq!(input_edit.start_position.row, 1);
+        assert_eq!(input_edit.old_end_position.row, 1);
+    }
+
+    #[test]
+    fn test_compute_input_edit_handles_a_multiline_insert() {
+        let editor_parsing = Arc::new(EditorParsing::default());
+        let document = DocumentEditLines::new(
+            "".to_owned(),
+            "one\ntwo".to_owned(),
+            "".to_owned(),
+            editor_parsing,
+            PositionEncoding::Utf32,
+        );
+        // a zero-width insert right after "one" of two brand new lines
+        let range = Range::new(Position::new(0, 3, 0), Position::new(0, 3, 0));
+        let input_edit = document
+            .compute_input_edit(range, "\nONE.FIVE\nONE.SEVEN")
+            .expect("both positions resolve");
+        assert_eq!(input_edit.start_byte, 3);
+        assert_eq!(input_edit.old_end_byte, 3);
+        assert_eq!(input_edit.new_end_byte, 3 + "\nONE.FIVE\nONE.SEVEN".len());
+        // the new end lands 2 rows further down, on the last inserted line
+        assert_eq!(input_edit.new_end_position.row, input_edit.start_position.row + 2);
+        assert_eq!(input_edit.new_end_position.column, "ONE.SEVEN".len());
+    }
+
+    #[test]
+    fn test_compute_input_edit_returns_none_when_the_range_falls_outside_the_document() {
+        let editor_parsing = Arc::new(EditorParsing::default());
+        let document = DocumentEditLines::new(
+            "".to_owned(),
+            "one line only".to_owned(),
+            "".to_owned(),
+            editor_parsing,
+            PositionEncoding::Utf32,
+        );
+        let range = Range::new(Position::new(5, 0, 0), Position::new(5, 1, 0));
+        assert!(document.compute_input_edit(range, "x").is_none());
+    }
+
+    #[test]
+    fn test_ngrams_builds_windows_in_order() {
+        let subtokens = vec!["read".to_owned(), "file".to_owned(), "sync".to_owned()];
+        let bigrams = ngrams(&subtokens, 2);
+        assert!(bigrams.contains("read file"));
+        assert!(bigrams.contains("file sync"));
+        assert_eq!(bigrams.len(), 2);
+
+        let trigrams = ngrams(&subtokens, 3);
+        assert!(trigrams.contains("read file sync"));
+        assert_eq!(trigrams.len(), 1);
+    }
+
+    #[test]
+    fn test_ngrams_is_empty_below_the_window_size() {
+        let subtokens = vec!["only_one".to_owned()];
+        assert!(ngrams(&subtokens, 2).is_empty());
+    }
+
+    #[test]
+    fn test_term_weights_gives_a_ubiquitous_term_a_weight_near_the_floor() {
+        let common = BagOfWords::new(vec!["self value return".to_owned()], 1, 1);
+        let rare = BagOfWords::new(vec!["self unique_identifier".to_owned()], 1, 1);
+        let another = BagOfWords::new(vec!["self value other".to_owned()], 1, 1);
+        let term_weights = TermWeights::from_snippets(&[common, rare, another]);
+
+        // "self" shows up in every snippet, so its weight sits at the +1.0
+        // floor; "unique" (the tokenizer splits `unique_identifier` into
+        // "unique" and "identifier") shows up in exactly one, so it keeps the
+        // full ln(N) boost and outweighs "self" by a wide margin.
+        assert!((term_weights.weight_of("self") - 1.0).abs() < 1e-6);
+        assert!(term_weights.weight_of("unique") > term_weights.weight_of("self"));
+    }
+
+    #[test]
+    fn test_term_weights_defaults_unseen_terms_to_one() {
+        let snippet = BagOfWords::new(vec!["known_term".to_owned()], 1, 1);
+        let term_weights = TermWeights::from_snippets(&[snippet]);
+        assert_eq!(term_weights.weight_of("never_seen"), 1.0);
+    }
+
+    #[test]
+    fn test_similarity_score_rewards_shared_phrases_over_shared_loose_words() {
+        let term_weights = TermWeights::default();
+        let weights = SimilarityWeights::default();
+
+        let base = BagOfWords::new(vec!["read the file sync".to_owned()], 1, 1);
+        let shares_phrase = BagOfWords::new(vec!["read the file sync now".to_owned()], 1, 1);
+        let shares_only_words = BagOfWords::new(vec!["sync file the read".to_owned()], 1, 1);
+
+        let phrase_score = base.similarity_score(&shares_phrase, &term_weights, &weights);
+        let scrambled_score = base.similarity_score(&shares_only_words, &term_weights, &weights);
+
+        // same unigrams either way, but only `shares_phrase` keeps the bigram
+        // and trigram runs intact, so it must score strictly higher.
+        assert!(phrase_score > scrambled_score);
+    }
+
+    #[test]
+    fn test_undo_redo_round_trips_through_content_at_revision() {
+        let editor_parsing = Arc::new(EditorParsing::default());
+        let mut document = DocumentEditLines::new(
+            "".to_owned(),
+            "FIRST LINE\nSECOND LINE".to_owned(),
+            "".to_owned(),
+            editor_parsing,
+            PositionEncoding::Utf32,
+        );
+        let range = Range::new(Position::new(1, 0, 0), Position::new(1, 6, 0));
+        document.content_change(range, "CHANGED".to_owned());
+        assert_eq!(document.get_content(), "FIRST LINE\nCHANGED LINE");
+        assert_eq!(document.content_at_revision(None), "FIRST LINE\nSECOND LINE");
+
+        assert!(document.undo());
+        assert_eq!(document.get_content(), "FIRST LINE\nSECOND LINE");
+        assert!(!document.undo());
+
+        assert!(document.redo());
+        assert_eq!(document.get_content(), "FIRST LINE\nCHANGED LINE");
+        assert!(!document.redo());
+    }
+
+    #[test]
+    fn test_undo_then_a_different_edit_keeps_the_undone_branch_reachable_by_id() {
+        let editor_parsing = Arc::new(EditorParsing::default());
+        let mut document = DocumentEditLines::new(
+            "".to_owned(),
+            "FIRST LINE\nSECOND LINE".to_owned(),
+            "".to_owned(),
+            editor_parsing,
+            PositionEncoding::Utf32,
+        );
+        let range = Range::new(Position::new(1, 0, 0), Position::new(1, 6, 0));
+        document.content_change(range, "BRANCH ONE".to_owned());
+        let branch_one_revision = document.current_revision.expect("just created a revision");
+        assert!(document.undo());
+
+        // a different edit made after undoing shouldn't erase the branch we
+        // undid out of - it should just stop being the one `redo` picks.
+        document.content_change(range, "BRANCH TWO".to_owned());
+        assert_eq!(document.get_content(), "FIRST LINE\nBRANCH TWO LINE");
+
+        assert_eq!(
+            document.content_at_revision(Some(branch_one_revision)),
+            "FIRST LINE\nBRANCH ONE LINE"
+        );
+        assert_eq!(
+            document.content_at_revision(document.current_revision),
+            "FIRST LINE\nBRANCH TWO LINE"
+        );
+
+        // redo from the root now finds the more recently created branch
+        assert!(document.undo());
+        assert!(document.redo());
+        assert_eq!(document.get_content(), "FIRST LINE\nBRANCH TWO LINE");
+    }
+
+    #[test]
+    fn test_crlf_content_round_trips_through_get_content_unchanged() {
+        let editor_parsing = Arc::new(EditorParsing::default());
+        let content = "FIRST LINE\r\nSECOND LINE\r\nTHIRD LINE";
+        let document = DocumentEditLines::new(
+            "".to_owned(),
+            content.to_owned(),
+            "".to_owned(),
+            editor_parsing,
+            PositionEncoding::Utf32,
+        );
+        assert_eq!(document.get_content(), content);
+    }
+
+    #[test]
+    fn test_mixed_line_endings_are_preserved_per_line() {
+        let editor_parsing = Arc::new(EditorParsing::default());
+        // CRLF-dominant file with one lone LF line in the middle - each line
+        // must keep its own ending rather than being normalized to whichever
+        // style is more common.
+        let content = "FIRST LINE\r\nSECOND LINE\nTHIRD LINE";
+        let document = DocumentEditLines::new(
+            "".to_owned(),
+            content.to_owned(),
+            "".to_owned(),
+            editor_parsing,
+            PositionEncoding::Utf32,
+        );
+        assert_eq!(document.get_content(), content);
+    }
+
+    #[test]
+    fn test_editing_a_line_in_a_crlf_file_keeps_its_ending() {
+        let editor_parsing = Arc::new(EditorParsing::default());
+        let mut document = DocumentEditLines::new(
+            "".to_owned(),
+            "FIRST LINE\r\nSECOND LINE\r\nTHIRD LINE".to_owned(),
+            "".to_owned(),
+            editor_parsing,
+            PositionEncoding::Utf32,
+        );
+        let range = Range::new(Position::new(1, 0, 0), Position::new(1, 6, 0));
+        document.content_change(range, "CHANGED".to_owned());
+        assert_eq!(
+            document.get_content(),
+            "FIRST LINE\r\nCHANGED LINE\r\nTHIRD LINE"
+        );
+    }
 }
\ No newline at end of file
@@ -0,0 +1,420 @@
+//! Parses the LSP snippet grammar (`$1`, `${1:default}`, `${1|a,b|}`, `$0`,
+//! `$TM_SELECTED_TEXT`, ...) used by completion items, so a flat string can be
+//! turned into both the plain text an editor should insert and the tabstop
+//! ranges it should let the user tab through, instead of being dropped to the
+//! ground as opaque text.
+
+use std::collections::HashMap;
+
+use crate::chunking::text_document::{Position, Range};
+
+/// One parsed element of a snippet body. A full snippet is a `Vec` of these,
+/// read left to right; `Placeholder`'s `default` is itself a nested `Vec` so
+/// placeholders can contain further tabstops (`${1:foo($2)}`).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SnippetElement {
+    Text(String),
+    /// A bare `$1` / `${1}` - a zero-width stop with nothing pre-filled.
+    Tabstop { index: u32 },
+    /// `${1:default text}` - a stop pre-filled with `default`, which the
+    /// user can type over or tab past unchanged.
+    Placeholder {
+        index: u32,
+        default: Vec<SnippetElement>,
+    },
+    /// `${1|one,two,three|}` - a stop whose pre-filled text is `options[0]`,
+    /// with the rest offered as alternatives by editors that support it.
+    Choice { index: u32, options: Vec<String> },
+    /// `$TM_SELECTED_TEXT` / `${TM_SELECTED_TEXT:fallback}` - not a tabstop;
+    /// we don't have the editor context to resolve these, so `default` (if
+    /// any) is rendered verbatim and an unknown variable with no default
+    /// renders as empty text.
+    Variable {
+        name: String,
+        default: Vec<SnippetElement>,
+    },
+}
+
+/// A snippet body parsed into its elements, ready to be [`render`](Self::render)ed
+/// against a concrete insertion point.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParsedSnippet {
+    elements: Vec<SnippetElement>,
+}
+
+/// The ranges belonging to a single tabstop index. More than one range means
+/// the index was used more than once in the snippet (linked tabstops, e.g.
+/// a placeholder repeated as both a parameter and a doc comment) - an editor
+/// is expected to keep every range in the group in sync as the user types.
+#[derive(Debug, Clone)]
+pub struct LinkedTabstop {
+    pub index: u32,
+    pub ranges: Vec<Range>,
+}
+
+/// The result of rendering a [`ParsedSnippet`]: the plain text to insert and
+/// where each tabstop landed within it.
+#[derive(Debug, Clone)]
+pub struct RenderedSnippet {
+    pub text: String,
+    /// Ordered the way an editor should walk them: ascending by index, with
+    /// `$0` (the final cursor position) always last regardless of where it
+    /// appeared in the source, per the LSP snippet spec.
+    pub tabstops: Vec<LinkedTabstop>,
+}
+
+impl ParsedSnippet {
+    pub fn parse(source: &str) -> Self {
+        let mut parser = Parser::new(source);
+        let elements = parser.parse_elements(false);
+        ParsedSnippet { elements }
+    }
+
+    /// Renders this snippet as if its first character were inserted at
+    /// `insertion_anchor`, producing the plain text and the tabstop ranges
+    /// (in this crate's `Position`/`Range` terms) relative to that point.
+    pub fn render(&self, insertion_anchor: Position) -> RenderedSnippet {
+        let mut renderer = Renderer::new(insertion_anchor);
+        renderer.render_elements(&self.elements);
+        renderer.finish()
+    }
+}
+
+struct Renderer {
+    position: Position,
+    text: String,
+    tabstops: HashMap<u32, Vec<Range>>,
+}
+
+impl Renderer {
+    fn new(start: Position) -> Self {
+        Renderer {
+            position: start,
+            text: String::new(),
+            tabstops: HashMap::new(),
+        }
+    }
+
+    fn push_char(&mut self, character: char) {
+        if character == '\n' {
+            self.position = Position::new(self.position.line() + 1, 0, 0);
+        } else {
+            self.position = Position::new(self.position.line(), self.position.column() + 1, 0);
+        }
+        self.text.push(character);
+    }
+
+    fn push_str(&mut self, text: &str) {
+        for character in text.chars() {
+            self.push_char(character);
+        }
+    }
+
+    fn render_elements(&mut self, elements: &[SnippetElement]) {
+        for element in elements {
+            self.render_element(element);
+        }
+    }
+
+    fn render_element(&mut self, element: &SnippetElement) {
+        match element {
+            SnippetElement::Text(text) => self.push_str(text),
+            SnippetElement::Tabstop { index } => {
+                let point = self.position.clone();
+                self.tabstops
+                    .entry(*index)
+                    .or_default()
+                    .push(Range::new(point.clone(), point));
+            }
+            SnippetElement::Placeholder { index, default } => {
+                let start = self.position.clone();
+                self.render_elements(default);
+                let end = self.position.clone();
+                self.tabstops
+                    .entry(*index)
+                    .or_default()
+                    .push(Range::new(start, end));
+            }
+            SnippetElement::Choice { index, options } => {
+                let start = self.position.clone();
+                self.push_str(options.first().map(String::as_str).unwrap_or(""));
+                let end = self.position.clone();
+                self.tabstops
+                    .entry(*index)
+                    .or_default()
+                    .push(Range::new(start, end));
+            }
+            SnippetElement::Variable { default, .. } => self.render_elements(default),
+        }
+    }
+
+    /// Consumes the renderer, sorting the collected tabstops into walk order
+    /// and conjuring an implicit `$0` at the end of the text when the
+    /// snippet never declared one explicitly.
+    fn finish(mut self) -> RenderedSnippet {
+        let final_position = self.position.clone();
+        self.tabstops
+            .entry(0)
+            .or_insert_with(|| vec![Range::new(final_position.clone(), final_position)]);
+        let mut tabstops = self
+            .tabstops
+            .into_iter()
+            .map(|(index, ranges)| LinkedTabstop { index, ranges })
+            .collect::<Vec<_>>();
+        tabstops.sort_by_key(|tabstop| if tabstop.index == 0 {
+            u32::MAX
+        } else {
+            tabstop.index
+        });
+        RenderedSnippet {
+            text: self.text,
+            tabstops,
+        }
+    }
+}
+
+fn is_variable_char(character: char) -> bool {
+    character.is_ascii_alphanumeric() || character == '_'
+}
+
+/// Recursive-descent parser over the snippet grammar. Malformed input never
+/// makes the parse fail: a `$` not followed by a digit, `{` or variable name
+/// is kept as literal text, a `${...` missing its closing `}` runs to the end
+/// of the input, a `${` with neither an index nor a name becomes an empty
+/// variable, and an index too large for `u32` reads as `0` - completions are
+/// best-effort UI, not something we want to reject wholesale over one bad
+/// snippet.
+struct Parser<'a> {
+    chars: std::iter::Peekable<std::str::Chars<'a>>,
+}
+
+impl<'a> Parser<'a> {
+    fn new(source: &'a str) -> Self {
+        Parser {
+            chars: source.chars().peekable(),
+        }
+    }
+
+    /// Parses a run of elements, stopping at an unescaped `}` when
+    /// `stop_at_close_brace` is set (used for a placeholder's nested
+    /// `default` elements), or at end of input otherwise.
+    fn parse_elements(&mut self, stop_at_close_brace: bool) -> Vec<SnippetElement> {
+        let mut elements = Vec::new();
+        let mut text = String::new();
+        while let Some(&character) = self.chars.peek() {
+            if stop_at_close_brace && character == '}' {
+                break;
+            }
+            if character == '\\' {
+                self.chars.next();
+                match self.chars.next() {
+                    Some(escaped @ ('$' | '}' | '\\')) => text.push(escaped),
+                    Some(other) => {
+                        text.push('\\');
+                        text.push(other);
+                    }
+                    None => text.push('\\'),
+                }
+                continue;
+            }
+            if character == '$' {
+                if !text.is_empty() {
+                    elements.push(SnippetElement::Text(std::mem::take(&mut text)));
+                }
+                elements.push(self.parse_dollar());
+                continue;
+            }
+            self.chars.next();
+            text.push(character);
+        }
+        if !text.is_empty() {
+            elements.push(SnippetElement::Text(text));
+        }
+        elements
+    }
+
+    /// Called right after peeking a `$`; consumes it and whatever tabstop,
+    /// placeholder, choice or variable follows.
+    fn parse_dollar(&mut self) -> SnippetElement {
+        self.chars.next();
+        match self.chars.peek().copied() {
+            Some('{') => {
+                self.chars.next();
+                self.parse_braced()
+            }
+            Some(character) if character.is_ascii_digit() => SnippetElement::Tabstop {
+                index: self.parse_int(),
+            },
+            Some(character) if is_variable_char(character) => SnippetElement::Variable {
+                name: self.parse_name(),
+                default: Vec::new(),
+            },
+            _ => SnippetElement::Text("$".to_string()),
+        }
+    }
+
+    /// Called right after consuming the `{` of a `${...}` form.
+    fn parse_braced(&mut self) -> SnippetElement {
+        if self
+            .chars
+            .peek()
+            .is_some_and(|character| character.is_ascii_digit())
+        {
+            let index = self.parse_int();
+            match self.chars.peek() {
+                Some(':') => {
+                    self.chars.next();
+                    let default = self.parse_elements(true);
+                    self.consume_close_brace();
+                    SnippetElement::Placeholder { index, default }
+                }
+                Some('|') => {
+                    self.chars.next();
+                    let options = self.parse_choice_options();
+                    self.consume_close_brace();
+                    SnippetElement::Choice { index, options }
+                }
+                _ => {
+                    self.consume_close_brace();
+                    SnippetElement::Tabstop { index }
+                }
+            }
+        } else {
+            let name = self.parse_name();
+            let default = if self.chars.peek() == Some(&':') {
+                self.chars.next();
+                self.parse_elements(true)
+            } else {
+                Vec::new()
+            };
+            self.consume_close_brace();
+            SnippetElement::Variable { name, default }
+        }
+    }
+
+    fn parse_int(&mut self) -> u32 {
+        let mut digits = String::new();
+        while let Some(&character) = self.chars.peek() {
+            if character.is_ascii_digit() {
+                digits.push(character);
+                self.chars.next();
+            } else {
+                break;
+            }
+        }
+        digits.parse().unwrap_or(0)
+    }
+
+    fn parse_name(&mut self) -> String {
+        let mut name = String::new();
+        while let Some(&character) = self.chars.peek() {
+            if is_variable_char(character) {
+                name.push(character);
+                self.chars.next();
+            } else {
+                break;
+            }
+        }
+        name
+    }
+
+    /// `${1|one,two|}` options: comma-separated, with `\,`, `\|` and `\\`
+    /// escaped within an option the same way top-level text escapes `$`/`}`.
+    fn parse_choice_options(&mut self) -> Vec<String> {
+        let mut options = Vec::new();
+        let mut current = String::new();
+        while let Some(&character) = self.chars.peek() {
+            if character == '|' {
+                break;
+            }
+            if character == '\\' {
+                self.chars.next();
+                match self.chars.next() {
+                    Some(escaped @ ('\\' | ',' | '|' | '}')) => current.push(escaped),
+                    Some(other) => {
+                        current.push('\\');
+                        current.push(other);
+                    }
+                    None => current.push('\\'),
+                }
+                continue;
+            }
+            if character == ',' {
+                self.chars.next();
+                options.push(std::mem::take(&mut current));
+                continue;
+            }
+            self.chars.next();
+            current.push(character);
+        }
+        options.push(current);
+        // consume the trailing `|` of `|}`, leaving the `}` for the caller
+        if self.chars.peek() == Some(&'|') {
+            self.chars.next();
+        }
+        options
+    }
+
+    fn consume_close_brace(&mut self) {
+        if self.chars.peek() == Some(&'}') {
+            self.chars.next();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chunking::text_document::Position;
+
+    fn anchor() -> Position {
+        Position::new(0, 0, 0)
+    }
+
+    #[test]
+    fn renders_plain_text_with_implicit_final_tabstop() {
+        let snippet = ParsedSnippet::parse("console.log()");
+        let rendered = snippet.render(anchor());
+        assert_eq!(rendered.text, "console.log()");
+        assert_eq!(rendered.tabstops.len(), 1);
+        assert_eq!(rendered.tabstops[0].index, 0);
+    }
+
+    #[test]
+    fn renders_placeholder_default_text_and_range() {
+        let snippet = ParsedSnippet::parse("for (${1:item} of ${2:items}) {\n\t$0\n}");
+        let rendered = snippet.render(anchor());
+        assert_eq!(rendered.text, "for (item of items) {\n\t\n}");
+        assert_eq!(rendered.tabstops.len(), 3);
+        assert_eq!(rendered.tabstops[0].index, 1);
+        assert_eq!(rendered.tabstops[1].index, 2);
+        assert_eq!(rendered.tabstops[2].index, 0);
+    }
+
+    #[test]
+    fn collapses_linked_tabstops_into_one_group() {
+        let snippet = ParsedSnippet::parse("<${1:div}></${1:div}>");
+        let rendered = snippet.render(anchor());
+        assert_eq!(rendered.text, "<div></div>");
+        let linked = rendered
+            .tabstops
+            .iter()
+            .find(|tabstop| tabstop.index == 1)
+            .unwrap();
+        assert_eq!(linked.ranges.len(), 2);
+    }
+
+    #[test]
+    fn choice_renders_its_first_option() {
+        let snippet = ParsedSnippet::parse("${1|foo,bar,baz|}");
+        let rendered = snippet.render(anchor());
+        assert_eq!(rendered.text, "foo");
+    }
+
+    #[test]
+    fn unescapes_dollar_brace_and_backslash() {
+        let snippet = ParsedSnippet::parse(r"\${not a tabstop\} \\");
+        let rendered = snippet.render(anchor());
+        assert_eq!(rendered.text, r"${not a tabstop} \");
+    }
+}